@@ -0,0 +1,5 @@
+-- Migration: Soft-delete notes into a trash
+ALTER TABLE notes ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_notes_user_id_deleted_at ON notes(user_id, deleted_at);
+CREATE INDEX idx_notes_deleted_at ON notes(deleted_at) WHERE deleted_at IS NOT NULL;
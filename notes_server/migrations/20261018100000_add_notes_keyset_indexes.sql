@@ -0,0 +1,4 @@
+-- Migration: Indexes backing keyset pagination of a user's notes
+CREATE INDEX idx_notes_user_id_updated_at_id ON notes(user_id, updated_at, id);
+CREATE INDEX idx_notes_user_id_created_at_id ON notes(user_id, created_at, id);
+CREATE INDEX idx_notes_user_id_title_id ON notes(user_id, title, id);
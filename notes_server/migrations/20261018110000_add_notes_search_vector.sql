@@ -0,0 +1,7 @@
+-- Migration: Full-text search over note titles and contents
+ALTER TABLE notes ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', COALESCE(title, '')), 'A') ||
+    setweight(to_tsvector('english', COALESCE(content, '')), 'B')
+) STORED;
+
+CREATE INDEX idx_notes_search_vector ON notes USING GIN (search_vector);
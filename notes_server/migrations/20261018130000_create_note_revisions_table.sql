@@ -0,0 +1,17 @@
+-- Migration: Create note_revisions table
+CREATE TABLE note_revisions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    note_id UUID NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+    revision_number INTEGER NOT NULL,
+    title VARCHAR(255),
+    content TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (note_id, revision_number)
+);
+
+CREATE INDEX idx_note_revisions_created_at ON note_revisions(created_at);
+
+-- Existing notes start their history at their current state
+INSERT INTO note_revisions (note_id, revision_number, title, content, created_at)
+SELECT id, 1, title, content, COALESCE(updated_at, NOW())
+FROM notes;
@@ -0,0 +1,2 @@
+-- Migration: Per-note version number for optimistic concurrency
+ALTER TABLE notes ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
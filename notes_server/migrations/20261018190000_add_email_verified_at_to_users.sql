@@ -0,0 +1,2 @@
+-- Migration: Track when a user proved ownership of their email address
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMP WITH TIME ZONE;
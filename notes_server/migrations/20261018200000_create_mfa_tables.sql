@@ -0,0 +1,20 @@
+-- Migration: Create tables for TOTP two-factor authentication and recovery codes
+CREATE TABLE totp_credentials (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    -- AES-GCM encrypted shared secret, never stored in plain text
+    secret_ciphertext TEXT NOT NULL,
+    -- NULL until the user proves their authenticator works with a first code
+    confirmed_at TIMESTAMP WITH TIME ZONE,
+    -- Last accepted time step, so a code can't be replayed within its window
+    last_used_step BIGINT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE TABLE recovery_codes (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash VARCHAR(64) NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    UNIQUE (user_id, code_hash)
+);
@@ -0,0 +1,8 @@
+-- Migration: Create login_attempts table to throttle failed logins per account and per IP
+CREATE TABLE login_attempts (
+    -- 'email:<address>' or 'ip:<address>'; unknown emails are tracked like real ones
+    key VARCHAR(300) PRIMARY KEY,
+    failed_count INTEGER NOT NULL DEFAULT 0,
+    last_failed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMP WITH TIME ZONE
+);
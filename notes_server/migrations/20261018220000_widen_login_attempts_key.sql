@@ -0,0 +1,5 @@
+-- Migration: Valid email addresses can be longer than the old key column allowed
+ALTER TABLE login_attempts ALTER COLUMN key TYPE TEXT;
+
+-- Old rows are purged by last_failed_at
+CREATE INDEX idx_login_attempts_last_failed_at ON login_attempts(last_failed_at);
@@ -0,0 +1,9 @@
+-- Search headlines are HTML: note text is escaped before ts_headline wraps
+-- matches in <mark>, so stored markup is shown rather than rendered. The
+-- parser reads the entities as single tokens, leaving the words around them
+-- to be matched as before.
+CREATE OR REPLACE FUNCTION escape_html(value TEXT)
+RETURNS TEXT AS $$
+    SELECT replace(replace(replace(replace(replace(value,
+        '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '"', '&quot;'), '''', '&#39;');
+$$ LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE;
@@ -0,0 +1,47 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::time::Instant;
+use tracing::{Instrument, field, info, info_span};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// Wraps each request in a span that everything logged while handling it
+// inherits, and writes one access log line when the response is ready.
+// `user_id` is filled in by `RequireAuth` once the caller is known.
+pub async fn access_log(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    let span = info_span!(
+        "request",
+        method = %request.method(),
+        path = %request.uri().path(),
+        route,
+        request_id,
+        user_id = field::Empty,
+    );
+
+    let started_at = Instant::now();
+    let response = next.run(request).instrument(span.clone()).await;
+    let latency_ms = started_at.elapsed().as_secs_f64() * 1000.0;
+
+    span.in_scope(|| {
+        info!(
+            status = response.status().as_u16(),
+            latency_ms, "request completed"
+        );
+    });
+
+    response
+}
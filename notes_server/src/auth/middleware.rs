@@ -1,50 +1,84 @@
-use crate::state::AppState;
+use crate::{errors::ApiError, metrics, state::AppState};
 use axum::{
     extract::{FromRef, FromRequestParts},
-    http::{HeaderMap, StatusCode, request::Parts},
+    http::{HeaderMap, request::Parts},
 };
-use services::User;
+use services::{
+    User,
+    errors::AuthError,
+    models::{Credential, Scope},
+};
+use tracing::{Span, field};
+use uuid::Uuid;
 
-// For protected routes - requires valid JWT
-pub struct RequireAuth(pub User);
+// For protected routes - requires a valid JWT or personal access token
+pub struct RequireAuth(pub User, pub Credential);
 
 impl<S> FromRequestParts<S> for RequireAuth
 where
     AppState: FromRef<S>,
     S: Send + Sync,
 {
-    type Rejection = StatusCode;
+    type Rejection = ApiError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let app_state = AppState::from_ref(state);
 
         // Extract Authorization header
         let headers = &parts.headers;
-        let token = extract_token_from_headers(headers).ok_or(StatusCode::UNAUTHORIZED)?;
+        let token = extract_token_from_headers(headers)
+            .ok_or_else(|| ApiError::unauthorized("Missing authorization token"))?;
 
         // Validate JWT token
-        let user_id = app_state
+        let principal = app_state
             .auth_service
             .validate_token(&token)
             .await
-            .map_err(|_| StatusCode::UNAUTHORIZED)?;
+            .inspect_err(|err| {
+                if matches!(err, AuthError::TokenValidationError) {
+                    metrics::record_token_validation_failure();
+                }
+            })?;
 
         // Get user from database
         let user = app_state
             .user_service
-            .find_user_by_id(user_id)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-            .ok_or(StatusCode::UNAUTHORIZED)?;
+            .find_user_by_id(principal.user_id)
+            .await?
+            .ok_or_else(|| ApiError::unauthorized("Invalid or expired token"))?;
+
+        // Shows up on the access log line for this request
+        Span::current().record("user_id", field::display(user.id));
 
-        Ok(RequireAuth(user))
+        Ok(RequireAuth(user, principal.credential))
     }
 }
 
-fn extract_token_from_headers(headers: &HeaderMap) -> Option<String> {
+pub fn extract_token_from_headers(headers: &HeaderMap) -> Option<String> {
     let auth_header = headers.get("Authorization")?.to_str().ok()?;
 
     auth_header
         .strip_prefix("Token ")
         .map(|token| token.to_string())
 }
+
+pub fn require_scope(credential: &Credential, scope: Scope) -> Result<(), ApiError> {
+    if credential.allows(scope) {
+        return Ok(());
+    }
+
+    Err(ApiError::forbidden(format!(
+        "This token is missing the {} scope",
+        scope.as_str()
+    )))
+}
+
+// Session management is only available to interactive logins, not to API tokens
+pub fn require_session(credential: &Credential) -> Result<Uuid, ApiError> {
+    match credential {
+        Credential::Session(session_id) => Ok(*session_id),
+        Credential::PersonalAccessToken { .. } => Err(ApiError::forbidden(
+            "This action is not available to personal access tokens",
+        )),
+    }
+}
@@ -0,0 +1,676 @@
+use axum::http::HeaderValue;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use ipnet::IpNet;
+use serde::{Deserialize, Deserializer};
+use std::{
+    env, fmt, fs, io,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+// notes.title and note_revisions.title are VARCHAR(255)
+const MAX_TITLE_COLUMN_LENGTH: usize = 255;
+
+// Settings come from an optional TOML file (CONFIG_FILE, default config.toml)
+// and can each be overridden by an environment variable. Every section and key
+// has a default except the database URL, signing key id and TOTP key.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub auth: AuthSettings,
+    pub mail: MailSettings,
+    pub notes: NoteSettings,
+    pub rate_limit: RateLimitSettings,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    pub bind_address: SocketAddr,
+    // Prometheus scrapes /metrics here; keep it off the public interface
+    pub metrics_bind_address: SocketAddr,
+    // Public base URL, used in emailed links and as the default JWT issuer
+    pub app_url: String,
+    // Browser origins allowed to call the API; empty disables CORS, "*" allows any
+    pub cors_origins: Vec<String>,
+    // A tracing filter such as "info" or "info,sqlx=warn"
+    pub log_level: String,
+    pub log_format: LogFormat,
+    // Proxies whose X-Forwarded-For / X-Real-IP headers are believed
+    #[serde(deserialize_with = "deserialize_ip_nets")]
+    pub trusted_proxies: Vec<IpNet>,
+    // On SIGTERM readiness fails for this long before the listener closes,
+    // giving load balancers time to stop sending new requests
+    pub shutdown_delay_secs: u64,
+    // In-flight requests still running after this are dropped
+    pub shutdown_drain_timeout_secs: u64,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            bind_address: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            metrics_bind_address: SocketAddr::from(([127, 0, 0, 1], 9090)),
+            app_url: "http://localhost:3000".to_string(),
+            cors_origins: Vec::new(),
+            log_level: "info".to_string(),
+            log_format: LogFormat::Json,
+            trusted_proxies: Vec::new(),
+            shutdown_delay_secs: 5,
+            shutdown_drain_timeout_secs: 30,
+        }
+    }
+}
+
+// JSON for log shippers, text for reading in a terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Json,
+    Text,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "json" => Ok(LogFormat::Json),
+            "text" => Ok(LogFormat::Text),
+            _ => Err("expected json or text".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseSettings {
+    pub url: Secret<String>,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_secs: u64,
+    pub idle_timeout_secs: u64,
+    // How long the readiness probe waits on the database before reporting it down
+    pub health_check_timeout_secs: u64,
+    pub run_migrations: bool,
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        Self {
+            url: Secret::default(),
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout_secs: 5,
+            idle_timeout_secs: 600,
+            health_check_timeout_secs: 2,
+            run_migrations: true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSettings {
+    pub jwt_keys_dir: PathBuf,
+    pub jwt_signing_key_id: String,
+    // Defaults to server.app_url
+    pub jwt_issuer: Option<String>,
+    pub jwt_audience: String,
+    pub access_token_ttl_secs: i64,
+    pub refresh_token_ttl_days: i64,
+    pub session_cache_ttl_secs: u64,
+    pub password_reset_ttl_mins: i64,
+    pub email_verification_ttl_hours: i64,
+    // Unverified accounts can sign in either way; this only gates creating notes
+    pub require_email_verification: bool,
+    pub mfa_challenge_ttl_secs: i64,
+    // 32 bytes as base64
+    pub totp_encryption_key: Secret<String>,
+    pub login_max_failed_attempts: i32,
+    pub login_ip_max_failed_attempts: i32,
+    pub login_lockout_mins: i64,
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+}
+
+impl Default for AuthSettings {
+    fn default() -> Self {
+        Self {
+            jwt_keys_dir: PathBuf::from("keys"),
+            jwt_signing_key_id: String::new(),
+            jwt_issuer: None,
+            jwt_audience: "notes-api".to_string(),
+            access_token_ttl_secs: 900,
+            refresh_token_ttl_days: 30,
+            session_cache_ttl_secs: 30,
+            password_reset_ttl_mins: 60,
+            email_verification_ttl_hours: 48,
+            require_email_verification: false,
+            mfa_challenge_ttl_secs: 300,
+            totp_encryption_key: Secret::default(),
+            login_max_failed_attempts: 10,
+            login_ip_max_failed_attempts: 50,
+            login_lockout_mins: 15,
+            argon2_memory_kib: 19 * 1024,
+            argon2_iterations: 2,
+            argon2_parallelism: 1,
+        }
+    }
+}
+
+impl AuthSettings {
+    pub fn totp_encryption_key(&self) -> Option<[u8; 32]> {
+        STANDARD
+            .decode(self.totp_encryption_key.expose())
+            .ok()
+            .and_then(|key| key.try_into().ok())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MailSettings {
+    // Emails are written here as .eml files until a real mailer is configured
+    pub spool_dir: PathBuf,
+    pub from: String,
+}
+
+impl Default for MailSettings {
+    fn default() -> Self {
+        Self {
+            spool_dir: PathBuf::from("mail_spool"),
+            from: "Notes <no-reply@localhost>".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoteSettings {
+    pub max_title_length: usize,
+    pub max_content_length: usize,
+    pub trash_retention_days: i64,
+    pub trash_purge_interval_secs: u64,
+    // Revision history is kept without limit when these are unset
+    pub revision_max_count: Option<i64>,
+    pub revision_max_age_days: Option<i64>,
+}
+
+impl Default for NoteSettings {
+    fn default() -> Self {
+        Self {
+            max_title_length: 50,
+            max_content_length: 500,
+            trash_retention_days: 30,
+            trash_purge_interval_secs: 3600,
+            revision_max_count: None,
+            revision_max_age_days: None,
+        }
+    }
+}
+
+// Requests per minute for each route group; 0 disables the limit
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitSettings {
+    pub auth_per_minute: u32,
+    pub write_per_minute: u32,
+    pub read_per_minute: u32,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            auth_per_minute: 20,
+            write_per_minute: 60,
+            read_per_minute: 300,
+        }
+    }
+}
+
+// Keeps a value out of `Debug` output so the config can be logged safely
+#[derive(Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[redacted]")
+    }
+}
+
+impl<T: FromStr> FromStr for Secret<T> {
+    type Err = T::Err;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value.parse().map(Secret)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            ConfigError::Parse(path, err) => write!(f, "invalid {}: {}", path.display(), err),
+            ConfigError::Invalid(problems) => {
+                write!(f, "invalid configuration:")?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn load() -> Result<Self, ConfigError> {
+        // Only a file that was asked for explicitly has to exist
+        let (path, required) = match env::var("CONFIG_FILE") {
+            Ok(path) => (PathBuf::from(path), true),
+            Err(_) => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+        };
+
+        let mut config = Self::from_file(&path, required)?;
+
+        let mut problems = Vec::new();
+        config.apply_env(&mut problems);
+        config.validate(&mut problems);
+
+        if !problems.is_empty() {
+            return Err(ConfigError::Invalid(problems));
+        }
+
+        Ok(config)
+    }
+
+    fn from_file(path: &Path, required: bool) -> Result<Self, ConfigError> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Self::default());
+            }
+            Err(err) => return Err(ConfigError::Read(path.to_path_buf(), err)),
+        };
+
+        toml::from_str(&contents).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))
+    }
+
+    fn apply_env(&mut self, problems: &mut Vec<String>) {
+        let server = &mut self.server;
+        env_override(&mut server.bind_address, "BIND_ADDRESS", problems);
+        env_override(
+            &mut server.metrics_bind_address,
+            "METRICS_BIND_ADDRESS",
+            problems,
+        );
+        env_override(&mut server.app_url, "APP_URL", problems);
+        env_override_list(&mut server.cors_origins, "CORS_ORIGINS", problems);
+        env_override(&mut server.log_level, "LOG_LEVEL", problems);
+        env_override(&mut server.log_format, "LOG_FORMAT", problems);
+        env_override(
+            &mut server.shutdown_delay_secs,
+            "SHUTDOWN_DELAY_SECS",
+            problems,
+        );
+        env_override(
+            &mut server.shutdown_drain_timeout_secs,
+            "SHUTDOWN_DRAIN_TIMEOUT_SECS",
+            problems,
+        );
+        env_override_with(
+            &mut server.trusted_proxies,
+            "TRUSTED_PROXIES",
+            problems,
+            |value| value.split(',').map(parse_ip_net).collect(),
+        );
+
+        let database = &mut self.database;
+        env_override(&mut database.url, "DATABASE_URL", problems);
+        env_override(
+            &mut database.max_connections,
+            "DATABASE_MAX_CONNECTIONS",
+            problems,
+        );
+        env_override(
+            &mut database.min_connections,
+            "DATABASE_MIN_CONNECTIONS",
+            problems,
+        );
+        env_override(
+            &mut database.acquire_timeout_secs,
+            "DATABASE_ACQUIRE_TIMEOUT_SECS",
+            problems,
+        );
+        env_override(
+            &mut database.idle_timeout_secs,
+            "DATABASE_IDLE_TIMEOUT_SECS",
+            problems,
+        );
+        env_override(
+            &mut database.health_check_timeout_secs,
+            "DATABASE_HEALTH_CHECK_TIMEOUT_SECS",
+            problems,
+        );
+        env_override(&mut database.run_migrations, "RUN_MIGRATIONS", problems);
+
+        let auth = &mut self.auth;
+        env_override(&mut auth.jwt_keys_dir, "JWT_KEYS_DIR", problems);
+        env_override(&mut auth.jwt_signing_key_id, "JWT_SIGNING_KEY_ID", problems);
+        env_override_optional(&mut auth.jwt_issuer, "JWT_ISSUER", problems);
+        env_override(&mut auth.jwt_audience, "JWT_AUDIENCE", problems);
+        env_override(
+            &mut auth.access_token_ttl_secs,
+            "ACCESS_TOKEN_TTL_SECS",
+            problems,
+        );
+        env_override(
+            &mut auth.refresh_token_ttl_days,
+            "REFRESH_TOKEN_TTL_DAYS",
+            problems,
+        );
+        env_override(
+            &mut auth.session_cache_ttl_secs,
+            "SESSION_CACHE_TTL_SECS",
+            problems,
+        );
+        env_override(
+            &mut auth.password_reset_ttl_mins,
+            "PASSWORD_RESET_TTL_MINS",
+            problems,
+        );
+        env_override(
+            &mut auth.email_verification_ttl_hours,
+            "EMAIL_VERIFICATION_TTL_HOURS",
+            problems,
+        );
+        env_override(
+            &mut auth.require_email_verification,
+            "REQUIRE_EMAIL_VERIFICATION",
+            problems,
+        );
+        env_override(
+            &mut auth.mfa_challenge_ttl_secs,
+            "MFA_CHALLENGE_TTL_SECS",
+            problems,
+        );
+        env_override(
+            &mut auth.totp_encryption_key,
+            "TOTP_ENCRYPTION_KEY",
+            problems,
+        );
+        env_override(
+            &mut auth.login_max_failed_attempts,
+            "LOGIN_MAX_FAILED_ATTEMPTS",
+            problems,
+        );
+        env_override(
+            &mut auth.login_ip_max_failed_attempts,
+            "LOGIN_IP_MAX_FAILED_ATTEMPTS",
+            problems,
+        );
+        env_override(&mut auth.login_lockout_mins, "LOGIN_LOCKOUT_MINS", problems);
+        env_override(&mut auth.argon2_memory_kib, "ARGON2_MEMORY_KIB", problems);
+        env_override(&mut auth.argon2_iterations, "ARGON2_ITERATIONS", problems);
+        env_override(&mut auth.argon2_parallelism, "ARGON2_PARALLELISM", problems);
+
+        let mail = &mut self.mail;
+        env_override(&mut mail.spool_dir, "MAIL_SPOOL_DIR", problems);
+        env_override(&mut mail.from, "MAIL_FROM", problems);
+
+        let notes = &mut self.notes;
+        env_override(
+            &mut notes.max_title_length,
+            "NOTE_MAX_TITLE_LENGTH",
+            problems,
+        );
+        env_override(
+            &mut notes.max_content_length,
+            "NOTE_MAX_CONTENT_LENGTH",
+            problems,
+        );
+        env_override(
+            &mut notes.trash_retention_days,
+            "TRASH_RETENTION_DAYS",
+            problems,
+        );
+        env_override(
+            &mut notes.trash_purge_interval_secs,
+            "TRASH_PURGE_INTERVAL_SECS",
+            problems,
+        );
+        env_override_optional(
+            &mut notes.revision_max_count,
+            "NOTE_REVISION_MAX_COUNT",
+            problems,
+        );
+        env_override_optional(
+            &mut notes.revision_max_age_days,
+            "NOTE_REVISION_MAX_AGE_DAYS",
+            problems,
+        );
+
+        let rate_limit = &mut self.rate_limit;
+        env_override(
+            &mut rate_limit.auth_per_minute,
+            "RATE_LIMIT_AUTH_PER_MINUTE",
+            problems,
+        );
+        env_override(
+            &mut rate_limit.write_per_minute,
+            "RATE_LIMIT_WRITE_PER_MINUTE",
+            problems,
+        );
+        env_override(
+            &mut rate_limit.read_per_minute,
+            "RATE_LIMIT_READ_PER_MINUTE",
+            problems,
+        );
+    }
+
+    // Collects every problem rather than stopping at the first one
+    fn validate(&self, problems: &mut Vec<String>) {
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+
+        let server = &self.server;
+        check(
+            server.metrics_bind_address != server.bind_address,
+            "server.metrics_bind_address (METRICS_BIND_ADDRESS) must differ from server.bind_address",
+        );
+        check(
+            server.app_url.starts_with("http://") || server.app_url.starts_with("https://"),
+            "server.app_url (APP_URL) must be an http:// or https:// URL",
+        );
+        check(
+            server
+                .cors_origins
+                .iter()
+                .all(|origin| is_valid_origin(origin))
+                && (server.cors_origins.len() <= 1
+                    || !server.cors_origins.iter().any(|origin| origin == "*")),
+            "server.cors_origins (CORS_ORIGINS) must be \"*\" or a list of origins like https://app.example.com",
+        );
+        check(
+            EnvFilter::try_new(&server.log_level).is_ok(),
+            "server.log_level (LOG_LEVEL) must be a level such as info or a filter such as info,sqlx=warn",
+        );
+        check(
+            server.shutdown_drain_timeout_secs > 0,
+            "server.shutdown_drain_timeout_secs (SHUTDOWN_DRAIN_TIMEOUT_SECS) must be at least 1",
+        );
+
+        let database = &self.database;
+        check(
+            !database.url.expose().is_empty(),
+            "database.url (DATABASE_URL) is required",
+        );
+        check(
+            database.max_connections > 0,
+            "database.max_connections (DATABASE_MAX_CONNECTIONS) must be at least 1",
+        );
+        check(
+            database.min_connections <= database.max_connections,
+            "database.min_connections (DATABASE_MIN_CONNECTIONS) cannot exceed database.max_connections",
+        );
+        check(
+            database.acquire_timeout_secs > 0,
+            "database.acquire_timeout_secs (DATABASE_ACQUIRE_TIMEOUT_SECS) must be at least 1",
+        );
+        check(
+            database.health_check_timeout_secs > 0,
+            "database.health_check_timeout_secs (DATABASE_HEALTH_CHECK_TIMEOUT_SECS) must be at least 1",
+        );
+
+        let auth = &self.auth;
+        check(
+            !auth.jwt_signing_key_id.is_empty(),
+            "auth.jwt_signing_key_id (JWT_SIGNING_KEY_ID) is required",
+        );
+        check(
+            auth.totp_encryption_key().is_some(),
+            "auth.totp_encryption_key (TOTP_ENCRYPTION_KEY) must be 32 bytes encoded as base64",
+        );
+        check(
+            [
+                auth.access_token_ttl_secs,
+                auth.refresh_token_ttl_days,
+                auth.password_reset_ttl_mins,
+                auth.email_verification_ttl_hours,
+                auth.mfa_challenge_ttl_secs,
+            ]
+            .iter()
+            .all(|ttl| *ttl > 0),
+            "auth token lifetimes must be positive",
+        );
+        check(
+            auth.login_max_failed_attempts > 0
+                && auth.login_ip_max_failed_attempts > 0
+                && auth.login_lockout_mins > 0,
+            "auth.login_* limits must be positive",
+        );
+
+        let notes = &self.notes;
+        check(
+            notes.max_title_length > 0 && notes.max_content_length > 0,
+            "notes.max_title_length and notes.max_content_length must be at least 1",
+        );
+        check(
+            notes.max_title_length <= MAX_TITLE_COLUMN_LENGTH,
+            "notes.max_title_length cannot exceed 255, the size of the title column",
+        );
+        check(
+            notes.trash_retention_days >= 0 && notes.trash_purge_interval_secs > 0,
+            "notes.trash_retention_days cannot be negative and notes.trash_purge_interval_secs must be at least 1",
+        );
+        check(
+            notes.revision_max_count.is_none_or(|count| count > 0)
+                && notes.revision_max_age_days.is_none_or(|days| days > 0),
+            "notes.revision_max_count and notes.revision_max_age_days must be positive when set",
+        );
+    }
+}
+
+fn env_override<T>(field: &mut T, name: &str, problems: &mut Vec<String>)
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    env_override_with(field, name, problems, |value| {
+        value.parse().map_err(|err: T::Err| err.to_string())
+    });
+}
+
+fn env_override_optional<T>(field: &mut Option<T>, name: &str, problems: &mut Vec<String>)
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    env_override_with(field, name, problems, |value| {
+        value
+            .parse()
+            .map(Some)
+            .map_err(|err: T::Err| err.to_string())
+    });
+}
+
+// Lists are comma-separated in the environment
+fn env_override_list(field: &mut Vec<String>, name: &str, problems: &mut Vec<String>) {
+    env_override_with(field, name, problems, |value| {
+        Ok(value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(str::to_string)
+            .collect())
+    });
+}
+
+fn env_override_with<T>(
+    field: &mut T,
+    name: &str,
+    problems: &mut Vec<String>,
+    parse: impl FnOnce(&str) -> Result<T, String>,
+) {
+    let Ok(value) = env::var(name) else {
+        return;
+    };
+
+    match parse(value.trim()) {
+        Ok(value) => *field = value,
+        Err(err) => problems.push(format!("{} is invalid: {}", name, err)),
+    }
+}
+
+// Accepts a single address as well as a CIDR range
+fn parse_ip_net(value: &str) -> Result<IpNet, String> {
+    let value = value.trim();
+
+    value
+        .parse::<IpNet>()
+        .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("{:?} is not an IP address or CIDR range", value))
+}
+
+fn deserialize_ip_nets<'de, D>(deserializer: D) -> Result<Vec<IpNet>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|value| parse_ip_net(value).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+fn is_valid_origin(origin: &str) -> bool {
+    origin == "*"
+        || ((origin.starts_with("http://") || origin.starts_with("https://"))
+            && !origin.ends_with('/')
+            && HeaderValue::from_str(origin).is_ok())
+}
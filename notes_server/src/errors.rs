@@ -0,0 +1,295 @@
+use axum::{
+    Json,
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use services::errors::{AuthError, NoteError, UserError};
+use std::collections::BTreeMap;
+use tracing::{debug, error};
+use utoipa::ToSchema;
+
+use crate::metrics;
+use validator::{ValidationErrors, ValidationErrorsKind};
+
+// Every error leaves the API as `{"error": {"code", "message", "fields"?}}`
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    fields: BTreeMap<String, Vec<String>>,
+    // Seconds, sent as a Retry-After header
+    retry_after: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorEnvelope<'a> {
+    error: ErrorBody<'a>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ErrorBody<'a> {
+    code: &'a str,
+    message: &'a str,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    fields: &'a BTreeMap<String, Vec<String>>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            fields: BTreeMap::new(),
+            retry_after: None,
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, "unauthorized", message)
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, "forbidden", message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found", message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, "conflict", message)
+    }
+
+    pub fn precondition_failed(message: impl Into<String>) -> Self {
+        Self::new(
+            StatusCode::PRECONDITION_FAILED,
+            "precondition_failed",
+            message,
+        )
+    }
+
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_data", message)
+    }
+
+    pub fn with_field(mut self, field: &str, message: impl Into<String>) -> Self {
+        self.fields
+            .entry(field.to_string())
+            .or_default()
+            .push(message.into());
+        self
+    }
+
+    // Rounds up so clients never retry a moment too early
+    pub fn with_retry_after(mut self, retry_after: chrono::Duration) -> Self {
+        let millis = retry_after.num_milliseconds().max(0) as u64;
+        self.retry_after = Some(millis.div_ceil(1000).max(1));
+        self
+    }
+
+    pub fn internal() -> Self {
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            "An unexpected error occurred",
+        )
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        // Server errors were already logged with their cause where they were mapped
+        if !self.status.is_server_error() {
+            debug!(status = self.status.as_u16(), code = self.code, message = %self.message, "request rejected");
+        }
+
+        let envelope = ErrorEnvelope {
+            error: ErrorBody {
+                code: self.code,
+                message: &self.message,
+                fields: &self.fields,
+            },
+        };
+
+        let mut response = (self.status, Json(envelope)).into_response();
+        if let Some(retry_after) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+
+        response
+    }
+}
+
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields = BTreeMap::new();
+        collect_field_errors(&errors, None, &mut fields);
+
+        Self {
+            fields,
+            ..Self::new(
+                StatusCode::BAD_REQUEST,
+                "validation_failed",
+                "One or more fields are invalid",
+            )
+        }
+    }
+}
+
+// Flattens nested validation errors into dotted field paths, e.g. `user.email`
+fn collect_field_errors(
+    errors: &ValidationErrors,
+    prefix: Option<&str>,
+    fields: &mut BTreeMap<String, Vec<String>>,
+) {
+    for (field, kind) in errors.errors() {
+        let path = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, field),
+            None => field.to_string(),
+        };
+
+        match kind {
+            ValidationErrorsKind::Field(field_errors) => {
+                let messages = fields.entry(path).or_default();
+                for error in field_errors {
+                    let message = match &error.message {
+                        Some(message) => message.to_string(),
+                        None => format!("Invalid value ({})", error.code),
+                    };
+                    messages.push(message);
+                }
+            }
+            ValidationErrorsKind::Struct(nested) => {
+                collect_field_errors(nested, Some(&path), fields);
+            }
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect_field_errors(nested, Some(&format!("{}[{}]", path, index)), fields);
+                }
+            }
+        }
+    }
+}
+
+impl From<AuthError> for ApiError {
+    fn from(err: AuthError) -> Self {
+        match err {
+            AuthError::UserNotFound | AuthError::InvalidPassword => Self::new(
+                StatusCode::UNAUTHORIZED,
+                "invalid_credentials",
+                "Invalid email or password",
+            ),
+            AuthError::UserAlreadyExists => {
+                Self::conflict("A user with this email or username already exists")
+            }
+            AuthError::TokenValidationError => Self::unauthorized("Invalid or expired token"),
+            AuthError::RefreshTokenReused => Self::new(
+                StatusCode::UNAUTHORIZED,
+                "refresh_token_reused",
+                "Refresh token has already been used; please log in again",
+            ),
+            AuthError::InvalidMfaCode => Self::new(
+                StatusCode::UNAUTHORIZED,
+                "invalid_mfa_code",
+                "Invalid two-factor authentication code",
+            ),
+            AuthError::MfaAlreadyEnabled => {
+                Self::conflict("Two-factor authentication is already enabled")
+            }
+            AuthError::MfaNotEnabled => Self::conflict("Two-factor authentication is not enabled"),
+            AuthError::TooManyAttempts { retry_after } => Self::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                "too_many_attempts",
+                "Too many failed login attempts; please try again later",
+            )
+            .with_retry_after(retry_after),
+            AuthError::AccountLocked { retry_after } => Self::new(
+                StatusCode::LOCKED,
+                "account_locked",
+                "This account is temporarily locked after too many failed login attempts",
+            )
+            .with_retry_after(retry_after),
+            AuthError::User(err) => err.into(),
+            AuthError::DatabaseError(err) => err.into(),
+            AuthError::PasswordHashError(_)
+            | AuthError::TokenGenerationError
+            | AuthError::MailDeliveryError(_)
+            | AuthError::SecretEncryptionError => {
+                error!(error = ?err, "auth error");
+                Self::internal()
+            }
+        }
+    }
+}
+
+impl From<UserError> for ApiError {
+    fn from(err: UserError) -> Self {
+        match err {
+            UserError::NotFound => Self::not_found("User not found"),
+            UserError::Forbidden => Self::forbidden("You are not allowed to perform this action"),
+            UserError::Conflict(message) => Self::conflict(message),
+            UserError::Validation(message) => Self::unprocessable(message),
+            UserError::Storage(err) => err.into(),
+        }
+    }
+}
+
+impl From<NoteError> for ApiError {
+    fn from(err: NoteError) -> Self {
+        match err {
+            NoteError::NotFound => Self::not_found("Note not found"),
+            NoteError::Forbidden => Self::forbidden("You are not allowed to access this note"),
+            NoteError::Conflict(message) => Self::conflict(message),
+            NoteError::VersionMismatch => {
+                Self::precondition_failed("Note has been modified since it was last fetched")
+            }
+            NoteError::Validation(message) => Self::unprocessable(message),
+            NoteError::Storage(err) => err.into(),
+        }
+    }
+}
+
+// Database failures are logged server-side and reported to clients without details
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        if matches!(err, sqlx::Error::PoolTimedOut) {
+            metrics::record_pool_timeout();
+        }
+        error!(error = ?err, "database error");
+        Self::internal()
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::new(rejection.status(), "invalid_body", rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::new(rejection.status(), "invalid_query", rejection.body_text())
+    }
+}
+
+impl From<axum_extra::extract::QueryRejection> for ApiError {
+    fn from(rejection: axum_extra::extract::QueryRejection) -> Self {
+        Self::new(rejection.status(), "invalid_query", rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        Self::new(rejection.status(), "invalid_path", rejection.body_text())
+    }
+}
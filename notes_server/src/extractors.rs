@@ -0,0 +1,218 @@
+use axum::{
+    extract::{ConnectInfo, FromRef, FromRequest, FromRequestParts},
+    http::{Extensions, HeaderMap, header::USER_AGENT, request::Parts},
+    response::{IntoResponse, Response},
+};
+use ipnet::IpNet;
+use serde::Serialize;
+use services::models::ClientInfo;
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+};
+
+use crate::{errors::ApiError, state::AppState};
+
+// Drop-in replacements for axum's extractors that reject with an `ApiError`
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct Query<T>(pub T);
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum_extra::extract::Query), rejection(ApiError))]
+pub struct MultiQuery<T>(pub T);
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct Path<T>(pub T);
+
+// User agent and peer address of the caller, recorded on sessions
+pub struct Client(pub ClientInfo);
+
+impl<S> FromRequestParts<S> for Client
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let app_state = AppState::from_ref(state);
+
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+
+        let ip_address = client_ip(
+            &parts.headers,
+            &parts.extensions,
+            &app_state.trusted_proxies,
+        )
+        .map(|ip| ip.to_string());
+
+        Ok(Client(ClientInfo {
+            user_agent,
+            ip_address,
+        }))
+    }
+}
+
+// The peer address, or the address a trusted proxy forwarded the request for.
+// X-Forwarded-For is read right to left so clients can't spoof their way past
+// the proxies we know about.
+pub fn client_ip(
+    headers: &HeaderMap,
+    extensions: &Extensions,
+    trusted_proxies: &[IpNet],
+) -> Option<IpAddr> {
+    let peer = extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())?;
+
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+    if !is_trusted(&peer) {
+        return Some(peer);
+    }
+
+    let forwarded_for: Vec<&str> = headers
+        .get_all("X-Forwarded-For")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+
+    // The first untrusted hop is the client. When every hop is one of our
+    // proxies the chain doesn't name it, so X-Real-IP gets a say first.
+    let mut leftmost_trusted = None;
+    for entry in forwarded_for.iter().rev() {
+        let Ok(ip) = entry.trim().parse::<IpAddr>() else {
+            break;
+        };
+        if !is_trusted(&ip) {
+            return Some(ip);
+        }
+        leftmost_trusted = Some(ip);
+    }
+
+    headers
+        .get("X-Real-IP")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .or(leftmost_trusted)
+        .or(Some(peer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trusted_proxies() -> Vec<IpNet> {
+        vec!["10.0.0.0/8".parse().unwrap()]
+    }
+
+    fn connected_from(peer: &str) -> Extensions {
+        let mut extensions = Extensions::new();
+        extensions.insert(ConnectInfo(SocketAddr::new(peer.parse().unwrap(), 443)));
+        extensions
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn ip(value: &str) -> Option<IpAddr> {
+        Some(value.parse().unwrap())
+    }
+
+    #[test]
+    fn ignores_spoofed_leftmost_forwarded_for_entry() {
+        // The client sent "1.1.1.1" itself; our proxy appended the real address
+        let headers = headers(&[("X-Forwarded-For", "1.1.1.1, 203.0.113.7, 10.0.0.2")]);
+
+        assert_eq!(
+            client_ip(&headers, &connected_from("10.0.0.1"), &trusted_proxies()),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn reads_forwarded_for_across_repeated_headers() {
+        let headers = headers(&[
+            ("X-Forwarded-For", "198.51.100.4"),
+            ("X-Forwarded-For", "10.0.0.2"),
+        ]);
+
+        assert_eq!(
+            client_ip(&headers, &connected_from("10.0.0.1"), &trusted_proxies()),
+            ip("198.51.100.4")
+        );
+    }
+
+    #[test]
+    fn all_trusted_chain_falls_back_to_real_ip() {
+        let headers = headers(&[
+            ("X-Forwarded-For", "10.0.0.3, 10.0.0.2"),
+            ("X-Real-IP", "203.0.113.7"),
+        ]);
+
+        assert_eq!(
+            client_ip(&headers, &connected_from("10.0.0.1"), &trusted_proxies()),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn all_trusted_chain_without_real_ip_uses_leftmost_hop() {
+        let headers = headers(&[("X-Forwarded-For", "10.0.0.3, 10.0.0.2")]);
+
+        assert_eq!(
+            client_ip(&headers, &connected_from("10.0.0.1"), &trusted_proxies()),
+            ip("10.0.0.3")
+        );
+    }
+
+    #[test]
+    fn untrusted_peer_headers_are_ignored() {
+        let headers = headers(&[
+            ("X-Forwarded-For", "203.0.113.7"),
+            ("X-Real-IP", "203.0.113.8"),
+        ]);
+
+        assert_eq!(
+            client_ip(
+                &headers,
+                &connected_from("198.51.100.9"),
+                &trusted_proxies()
+            ),
+            ip("198.51.100.9")
+        );
+    }
+
+    #[test]
+    fn trusted_peer_without_headers_is_the_client() {
+        assert_eq!(
+            client_ip(
+                &HeaderMap::new(),
+                &connected_from("10.0.0.1"),
+                &trusted_proxies()
+            ),
+            ip("10.0.0.1")
+        );
+    }
+}
@@ -1,3 +1,6 @@
 pub mod auth;
 pub mod health;
+pub mod metrics;
 pub mod note;
+pub mod tag;
+pub mod well_known;
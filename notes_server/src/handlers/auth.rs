@@ -1,88 +1,424 @@
-use crate::{auth::middleware::RequireAuth, schemas::auth_schemas::*, state::AppState};
-use axum::{Json, extract::State, http::StatusCode};
-use services::services::traits::AuthError;
+use crate::{
+    auth::middleware::{RequireAuth, require_scope, require_session},
+    errors::{ApiError, ErrorEnvelope},
+    extractors::{Client, Json, Path, Query},
+    metrics::{self, LoginResult, LoginStep},
+    schemas::auth_schemas::*,
+    state::AppState,
+};
+use axum::{extract::State, http::StatusCode};
+use chrono::{Duration, Utc};
+use services::{
+    errors::AuthError,
+    models::{LoginOutcome, Scope},
+};
+use tracing::{instrument, warn};
+use uuid::Uuid;
 use validator::Validate;
 
+#[utoipa::path(
+    post,
+    path = "/register",
+    tag = "auth",
+    request_body = RegisterUserRequest,
+    responses(
+        (status = 200, description = "Account created and signed in", body = UserResponse),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 409, description = "Email or username already taken", body = ErrorEnvelope),
+    )
+)]
+#[instrument(skip_all)]
 pub async fn register(
     State(state): State<AppState>,
+    Client(client): Client,
     Json(payload): Json<RegisterUserRequest>,
-) -> Result<Json<UserResponse>, StatusCode> {
+) -> Result<Json<UserResponse>, ApiError> {
     // Validate input data
-    payload
-        .user
-        .validate()
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    payload.user.validate()?;
 
     // Call auth service
-    let (user, token) = state
+    let (user, tokens) = state
         .auth_service
         .register_user(
             &payload.user.username,
             &payload.user.email,
             &payload.user.password,
+            &client,
         )
-        .await
-        .map_err(|err| match err {
-            AuthError::UserAlreadyExists => StatusCode::CONFLICT,
-            AuthError::PasswordHashError | AuthError::TokenGenerationError => {
-                StatusCode::INTERNAL_SERVER_ERROR
-            }
-            AuthError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
+        .await?;
 
     // Build response
-    let user_data = UserData::from_user_with_token(user, token);
+    let user_data = UserData::from_user_with_tokens(user, tokens);
     let response = UserResponse { user: user_data };
 
     Ok(Json(response))
 }
 
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "auth",
+    request_body = LoginUserRequest,
+    responses(
+        (status = 200, description = "Signed in, or a two-factor challenge to answer at /api/auth/mfa/verify", body = LoginResponse),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Invalid email or password", body = ErrorEnvelope),
+        (status = 423, description = "Account temporarily locked", body = ErrorEnvelope),
+        (status = 429, description = "Too many failed attempts", body = ErrorEnvelope),
+    )
+)]
+#[instrument(skip_all)]
 pub async fn login(
     State(state): State<AppState>,
+    Client(client): Client,
     Json(payload): Json<LoginUserRequest>,
-) -> Result<Json<UserResponse>, StatusCode> {
+) -> Result<Json<LoginResponse>, ApiError> {
     // Validate input
-    payload
-        .user
-        .validate()
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    payload.user.validate()?;
 
     // Call auth service
-    let (user, token) = state
+    let outcome = state
+        .auth_service
+        .login_user(&payload.user.email, &payload.user.password, &client)
+        .await;
+
+    metrics::record_login(
+        LoginStep::Password,
+        match &outcome {
+            Ok(LoginOutcome::Authenticated(..)) => LoginResult::Success,
+            Ok(LoginOutcome::MfaRequired { .. }) => LoginResult::MfaRequired,
+            Err(err) => metrics::login_failure(err),
+        },
+    );
+    let outcome = outcome?;
+
+    // Build response
+    let response = match outcome {
+        LoginOutcome::Authenticated(user, tokens) => {
+            let user_data = UserData::from_user_with_tokens(user, tokens);
+            LoginResponse::Authenticated(UserResponse { user: user_data })
+        }
+        LoginOutcome::MfaRequired { mfa_token } => {
+            LoginResponse::MfaRequired(MfaChallengeResponse::new(mfa_token))
+        }
+    };
+
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/mfa/verify",
+    tag = "auth",
+    request_body = MfaVerifyRequest,
+    responses(
+        (status = 200, description = "Signed in", body = UserResponse),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Invalid code or expired challenge", body = ErrorEnvelope),
+    )
+)]
+#[instrument(skip_all)]
+pub async fn verify_mfa(
+    State(state): State<AppState>,
+    Client(client): Client,
+    Json(payload): Json<MfaVerifyRequest>,
+) -> Result<Json<UserResponse>, ApiError> {
+    // Validate input
+    payload.validate()?;
+
+    let result = state
+        .auth_service
+        .verify_mfa(&payload.mfa_token, &payload.code, &client)
+        .await;
+
+    metrics::record_login(
+        LoginStep::Mfa,
+        match &result {
+            Ok(_) => LoginResult::Success,
+            Err(err) => metrics::login_failure(err),
+        },
+    );
+    let (user, tokens) = result?;
+
+    // Build response
+    let user_data = UserData::from_user_with_tokens(user, tokens);
+    let response = UserResponse { user: user_data };
+
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/mfa/totp",
+    tag = "auth",
+    responses(
+        (status = 200, description = "TOTP secret to add to an authenticator app", body = TotpEnrollmentResponse),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Requires a session, not a personal access token", body = ErrorEnvelope),
+        (status = 409, description = "Two-factor authentication is already enabled", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn enroll_totp(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+) -> Result<Json<TotpEnrollmentResponse>, ApiError> {
+    require_session(&credential)?;
+
+    let enrollment = state.auth_service.enroll_totp(&user).await?;
+
+    Ok(Json(enrollment.into()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/mfa/totp/confirm",
+    tag = "auth",
+    request_body = MfaCodeRequest,
+    responses(
+        (status = 200, description = "Two-factor authentication enabled", body = RecoveryCodesResponse),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Requires a session, not a personal access token", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn confirm_totp(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Json(payload): Json<MfaCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, ApiError> {
+    require_session(&credential)?;
+
+    // Validate input
+    payload.validate()?;
+
+    let recovery_codes = state
         .auth_service
-        .login_user(&payload.user.email, &payload.user.password)
+        .confirm_totp(user.id, &payload.code)
         .await
-        .map_err(|err| match err {
-            AuthError::UserNotFound | AuthError::InvalidPassword => StatusCode::UNAUTHORIZED,
-            AuthError::PasswordHashError | AuthError::TokenGenerationError => {
-                StatusCode::INTERNAL_SERVER_ERROR
-            }
-            AuthError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
+        .map_err(invalid_code_error)?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/mfa/totp/disable",
+    tag = "auth",
+    request_body = MfaCodeRequest,
+    responses(
+        (status = 204, description = "Two-factor authentication disabled"),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Requires a session, not a personal access token", body = ErrorEnvelope),
+        (status = 409, description = "Two-factor authentication is not enabled", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn disable_totp(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Json(payload): Json<MfaCodeRequest>,
+) -> Result<StatusCode, ApiError> {
+    require_session(&credential)?;
+
+    // Validate input
+    payload.validate()?;
+
+    state
+        .auth_service
+        .disable_totp(user.id, &payload.code)
+        .await
+        .map_err(invalid_code_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/mfa/recovery-codes",
+    tag = "auth",
+    request_body = MfaCodeRequest,
+    responses(
+        (status = 200, description = "New recovery codes; the old ones stop working", body = RecoveryCodesResponse),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Requires a session, not a personal access token", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn regenerate_recovery_codes(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Json(payload): Json<MfaCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, ApiError> {
+    require_session(&credential)?;
+
+    // Validate input
+    payload.validate()?;
+
+    let recovery_codes = state
+        .auth_service
+        .regenerate_recovery_codes(user.id, &payload.code)
+        .await
+        .map_err(invalid_code_error)?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+// The caller is already signed in, so a wrong code is a bad request rather than a 401
+fn invalid_code_error(err: AuthError) -> ApiError {
+    match err {
+        AuthError::InvalidMfaCode => {
+            ApiError::bad_request("Invalid two-factor authentication code")
+                .with_field("code", "Invalid two-factor authentication code")
+        }
+        err => err.into(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/refresh",
+    tag = "auth",
+    request_body = RefreshTokenRequest,
+    responses(
+        (status = 200, description = "New access and refresh tokens", body = UserResponse),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Invalid, expired or reused refresh token", body = ErrorEnvelope),
+    )
+)]
+#[instrument(skip_all)]
+pub async fn refresh(
+    State(state): State<AppState>,
+    Client(client): Client,
+    Json(payload): Json<RefreshTokenRequest>,
+) -> Result<Json<UserResponse>, ApiError> {
+    // Validate input
+    payload.validate()?;
+
+    // Rotate the refresh token
+    let (user, tokens) = state
+        .auth_service
+        .refresh_tokens(&payload.refresh_token, &client)
+        .await?;
 
     // Build response
-    let user_data = UserData::from_user_with_token(user, token);
+    let user_data = UserData::from_user_with_tokens(user, tokens);
     let response = UserResponse { user: user_data };
 
     Ok(Json(response))
 }
 
+#[utoipa::path(
+    get,
+    path = "/user",
+    tag = "users",
+    responses(
+        (status = 200, description = "The signed-in user", body = UserResponse),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the profile:read scope", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
 pub async fn current_user(
-    RequireAuth(user): RequireAuth,
+    RequireAuth(user, credential): RequireAuth,
     State(state): State<AppState>,
-) -> Result<Json<UserResponse>, StatusCode> {
+) -> Result<Json<UserResponse>, ApiError> {
+    require_scope(&credential, Scope::ProfileRead)?;
+
     // Call auth service
-    let (user, token) =
+    let (user, token) = state
+        .auth_service
+        .get_current_user(user, &credential)
+        .await?;
+
+    // Build response
+    let user_data = UserData::from_user_with_token(user, token);
+    let response = UserResponse { user: user_data };
+
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    put,
+    path = "/user",
+    tag = "users",
+    request_body = UpdateUserRequest,
+    responses(
+        (status = 200, description = "Profile updated", body = UserResponse),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the profile:write scope, or a personal access token tried to change the email", body = ErrorEnvelope),
+        (status = 409, description = "Email or username already taken", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn update_current_user(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Json(payload): Json<UpdateUserRequest>,
+) -> Result<Json<UserResponse>, ApiError> {
+    require_scope(&credential, Scope::ProfileWrite)?;
+
+    // Validate input
+    payload.user.validate()?;
+
+    // Password resets go to this address, so a leaked API token mustn't be able to move it
+    if payload.user.email.is_some() {
+        require_session(&credential)?;
+
+        let current_password = payload.user.current_password.as_deref().ok_or_else(|| {
+            ApiError::bad_request("Current password is required to change the email")
+                .with_field("current_password", "Current password is required")
+        })?;
         state
             .auth_service
-            .get_current_user(user)
+            .verify_password(&user, current_password)
             .await
             .map_err(|err| match err {
-                AuthError::TokenGenerationError => StatusCode::INTERNAL_SERVER_ERROR,
-                _ => StatusCode::INTERNAL_SERVER_ERROR,
+                AuthError::InvalidPassword => {
+                    ApiError::bad_request("Current password is incorrect")
+                        .with_field("current_password", "Current password is incorrect")
+                }
+                err => err.into(),
             })?;
+    }
+
+    let previous_email = user.email;
+
+    // Call user service
+    let user = state
+        .user_service
+        .update_user(
+            user.id,
+            payload.user.username.as_deref(),
+            payload.user.email.as_deref(),
+            payload.user.bio.as_ref().map(Option::as_deref),
+            payload.user.image.as_ref().map(Option::as_deref),
+        )
+        .await?
+        .ok_or_else(|| ApiError::not_found("User not found"))?;
+
+    // A changed address has to be confirmed again; the change itself has already been
+    // saved, so a failed email only means resending later
+    if user.email != previous_email
+        && let Err(err) = state.auth_service.send_verification_email(&user).await
+    {
+        warn!(error = %err, "failed to send verification email");
+    }
+
+    let (user, token) = state
+        .auth_service
+        .get_current_user(user, &credential)
+        .await?;
 
     // Build response
     let user_data = UserData::from_user_with_token(user, token);
@@ -90,3 +426,379 @@ pub async fn current_user(
 
     Ok(Json(response))
 }
+
+#[utoipa::path(
+    post,
+    path = "/user/password",
+    tag = "users",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 204, description = "Password changed; other sessions are signed out and personal access tokens revoked"),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Requires a session, not a personal access token", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn change_password(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> Result<StatusCode, ApiError> {
+    let session_id = require_session(&credential)?;
+
+    // Validate input
+    payload.validate()?;
+
+    state
+        .auth_service
+        .change_password(
+            &user,
+            session_id,
+            &payload.current_password,
+            &payload.new_password,
+        )
+        .await
+        .map_err(|err| match err {
+            AuthError::InvalidPassword => ApiError::bad_request("Current password is incorrect")
+                .with_field("current_password", "Current password is incorrect"),
+            err => err.into(),
+        })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/password-reset/request",
+    tag = "auth",
+    request_body = PasswordResetRequest,
+    responses(
+        (status = 202, description = "A reset link is emailed if the address belongs to an account"),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+    )
+)]
+#[instrument(skip_all)]
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(payload): Json<PasswordResetRequest>,
+) -> Result<StatusCode, ApiError> {
+    // Validate input
+    payload.validate()?;
+
+    // Always accepted, whether or not the email belongs to an account
+    state
+        .auth_service
+        .request_password_reset(&payload.email)
+        .await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[utoipa::path(
+    post,
+    path = "/password-reset/confirm",
+    tag = "auth",
+    request_body = PasswordResetConfirmRequest,
+    responses(
+        (status = 204, description = "Password reset; every session is signed out and personal access tokens revoked"),
+        (status = 400, description = "Invalid request or expired reset token", body = ErrorEnvelope),
+    )
+)]
+#[instrument(skip_all)]
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(payload): Json<PasswordResetConfirmRequest>,
+) -> Result<StatusCode, ApiError> {
+    // Validate input
+    payload.validate()?;
+
+    state
+        .auth_service
+        .reset_password(&payload.token, &payload.new_password)
+        .await
+        .map_err(|err| match err {
+            AuthError::TokenValidationError => {
+                ApiError::bad_request("Invalid or expired reset token")
+            }
+            err => err.into(),
+        })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/verify",
+    tag = "auth",
+    params(VerifyEmailQuery),
+    responses(
+        (status = 200, description = "Email address verified", body = UserResponse),
+        (status = 400, description = "Invalid or expired verification token", body = ErrorEnvelope),
+    )
+)]
+#[instrument(skip_all)]
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Query(query): Query<VerifyEmailQuery>,
+) -> Result<Json<UserResponse>, ApiError> {
+    let user = state
+        .auth_service
+        .verify_email(&query.token)
+        .await
+        .map_err(|err| match err {
+            AuthError::TokenValidationError => {
+                ApiError::bad_request("Invalid or expired verification token")
+            }
+            err => err.into(),
+        })?;
+
+    // Build response
+    let user_data = UserData::from_user_with_token(user, None);
+    let response = UserResponse { user: user_data };
+
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/verify/resend",
+    tag = "auth",
+    responses(
+        (status = 202, description = "Verification email sent"),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the profile:write scope", body = ErrorEnvelope),
+        (status = 409, description = "Email is already verified", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn resend_verification_email(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+) -> Result<StatusCode, ApiError> {
+    require_scope(&credential, Scope::ProfileWrite)?;
+
+    if user.email_verified_at.is_some() {
+        return Err(ApiError::conflict("Email is already verified"));
+    }
+
+    state.auth_service.send_verification_email(&user).await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[utoipa::path(
+    post,
+    path = "/logout",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Current session revoked"),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Requires a session, not a personal access token", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn logout(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+) -> Result<StatusCode, ApiError> {
+    let session_id = require_session(&credential)?;
+
+    // Revoke the session the access token belongs to
+    state
+        .auth_service
+        .revoke_session(user.id, session_id)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/logout-all",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Every session revoked"),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Requires a session, not a personal access token", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn logout_all(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+) -> Result<StatusCode, ApiError> {
+    require_session(&credential)?;
+
+    state.auth_service.revoke_all_sessions(user.id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/sessions",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Active sessions", body = SessionListResponse),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Requires a session, not a personal access token", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn find_sessions(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+) -> Result<Json<SessionListResponse>, ApiError> {
+    let current_session_id = require_session(&credential)?;
+
+    let sessions = state.auth_service.find_sessions(user.id).await?;
+
+    let response = SessionListResponse::from_sessions(sessions, Some(current_session_id));
+
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/sessions/{id}",
+    tag = "auth",
+    params(("id" = Uuid, Path, description = "Session id")),
+    responses(
+        (status = 204, description = "Session revoked"),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Requires a session, not a personal access token", body = ErrorEnvelope),
+        (status = 404, description = "Session not found", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all, fields(session_id = %session_id))]
+pub async fn revoke_session(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Path(session_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    require_session(&credential)?;
+
+    let revoked = state
+        .auth_service
+        .revoke_session(user.id, session_id)
+        .await?;
+
+    if !revoked {
+        return Err(ApiError::not_found("Session not found"));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/tokens",
+    tag = "auth",
+    request_body = CreatePersonalAccessTokenRequest,
+    responses(
+        (status = 200, description = "Token created; the secret is only returned here", body = PersonalAccessTokenResponse),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Requires a session, not a personal access token", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn create_personal_access_token(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Json(payload): Json<CreatePersonalAccessTokenRequest>,
+) -> Result<Json<PersonalAccessTokenResponse>, ApiError> {
+    require_session(&credential)?;
+
+    // Validate input
+    payload.token.validate()?;
+
+    let expires_at = payload
+        .token
+        .expires_in_days
+        .map(|days| Utc::now() + Duration::days(days));
+
+    let (token, secret) = state
+        .auth_service
+        .create_personal_access_token(
+            user.id,
+            &payload.token.name,
+            &payload.token.scopes,
+            expires_at,
+        )
+        .await?;
+
+    // The secret is only ever returned here
+    let response = PersonalAccessTokenResponse {
+        token: PersonalAccessTokenData::from_token_with_secret(token, secret),
+    };
+
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tokens",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Personal access tokens, without their secrets", body = PersonalAccessTokenListResponse),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Requires a session, not a personal access token", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn find_personal_access_tokens(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+) -> Result<Json<PersonalAccessTokenListResponse>, ApiError> {
+    require_session(&credential)?;
+
+    let tokens = state
+        .auth_service
+        .find_personal_access_tokens(user.id)
+        .await?;
+
+    Ok(Json(PersonalAccessTokenListResponse::from_tokens(tokens)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/tokens/{id}",
+    tag = "auth",
+    params(("id" = Uuid, Path, description = "Token id")),
+    responses(
+        (status = 204, description = "Token revoked"),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Requires a session, not a personal access token", body = ErrorEnvelope),
+        (status = 404, description = "Token not found", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all, fields(token_id = %token_id))]
+pub async fn revoke_personal_access_token(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Path(token_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    require_session(&credential)?;
+
+    let revoked = state
+        .auth_service
+        .revoke_personal_access_token(user.id, token_id)
+        .await?;
+
+    if !revoked {
+        return Err(ApiError::not_found("Token not found"));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,20 +1,217 @@
-use crate::state::AppState;
-use axum::{Json, extract::State};
-use serde_json::{Value, json};
-
-pub async fn health_check(State(state): State<AppState>) -> Json<Value> {
-    match sqlx::query("SELECT 1").execute(&state.db).await {
-        Ok(_) => Json(json!({
-            "status": "ok",
-            "database": "connected"
-        })),
-        Err(e) => {
-            eprintln!("Database error: {}", e);
-            Json(json!({
-                "status": "error",
-                "database": "disconnected",
-                "error": e.to_string()
-            }))
+use crate::{
+    metrics::pool_saturated,
+    schemas::health_schemas::{
+        BuildInfo, DatabaseCheck, HealthResponse, HealthStatus, MigrationCheck, PoolCheck,
+        ReadinessChecks, ReadinessResponse,
+    },
+    state::{AppState, MIGRATOR},
+};
+use axum::{Json, extract::State, http::StatusCode};
+use sqlx::PgPool;
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+use tokio::time::timeout;
+use tracing::warn;
+
+// The process is up and serving requests; dependencies are readiness's concern
+#[utoipa::path(
+    get,
+    path = "/live",
+    tag = "health",
+    responses(
+        (status = 200, description = "The process is running", body = HealthResponse),
+    )
+)]
+pub async fn liveness() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: HealthStatus::Up,
+    })
+}
+
+// 503 tells the load balancer to stop routing here until every check passes,
+// and from the moment a shutdown starts. Which check failed is only told on
+// the metrics port, so the public answer gives nothing about the deployment away.
+#[utoipa::path(
+    get,
+    path = "/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "Ready to serve traffic", body = HealthResponse),
+        (status = 503, description = "A check failed or the server is shutting down", body = HealthResponse),
+    )
+)]
+pub async fn readiness(State(state): State<AppState>) -> (StatusCode, Json<HealthResponse>) {
+    let (code, readiness) = check_readiness(&state).await;
+
+    (
+        code,
+        Json(HealthResponse {
+            status: readiness.status,
+        }),
+    )
+}
+
+// Per-check details for operators; served on the metrics port only
+pub async fn readiness_details(
+    State(state): State<AppState>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let (code, readiness) = check_readiness(&state).await;
+
+    (code, Json(readiness))
+}
+
+async fn check_readiness(state: &AppState) -> (StatusCode, ReadinessResponse) {
+    // Sampled first so the connection the checks borrow isn't counted against us
+    let pool = check_pool(&state.db);
+    let database = check_database(&state.db, state.health_check_timeout).await;
+    let migrations = if database.status == HealthStatus::Up {
+        check_migrations(&state.db, state.health_check_timeout).await
+    } else {
+        migrations_unknown("database unavailable")
+    };
+
+    let checks = ReadinessChecks {
+        database,
+        migrations,
+        pool,
+    };
+    let shutting_down = state.shutdown.is_cancelled();
+    let status = if shutting_down {
+        HealthStatus::Down
+    } else {
+        checks.status()
+    };
+    let code = match status {
+        HealthStatus::Up => StatusCode::OK,
+        HealthStatus::Down => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    (
+        code,
+        ReadinessResponse {
+            status,
+            build: BuildInfo::current(),
+            shutting_down,
+            checks,
+        },
+    )
+}
+
+async fn check_database(db: &PgPool, limit: Duration) -> DatabaseCheck {
+    let started_at = Instant::now();
+
+    let error = match timeout(limit, sqlx::query("SELECT 1").execute(db)).await {
+        Ok(Ok(_)) => {
+            return DatabaseCheck {
+                status: HealthStatus::Up,
+                latency_ms: Some(started_at.elapsed().as_millis() as u64),
+                error: None,
+            };
+        }
+        Ok(Err(err)) => {
+            warn!(error = %err, "readiness check: database query failed");
+            "query failed"
+        }
+        Err(_) => {
+            warn!(
+                timeout_secs = limit.as_secs(),
+                "readiness check: database timed out"
+            );
+            "timed out"
+        }
+    };
+
+    DatabaseCheck {
+        status: HealthStatus::Down,
+        latency_ms: None,
+        error: Some(error),
+    }
+}
+
+// Compares the migrations compiled into this binary with what the database has applied
+async fn check_migrations(db: &PgPool, limit: Duration) -> MigrationCheck {
+    let history = timeout(
+        limit,
+        sqlx::query_as::<_, (i64, bool)>("SELECT version, success FROM _sqlx_migrations")
+            .fetch_all(db),
+    )
+    .await;
+
+    let history = match history {
+        Ok(Ok(history)) => history,
+        Ok(Err(err)) => {
+            warn!(error = %err, "readiness check: cannot read migration history");
+            return migrations_unknown("cannot read migration history");
         }
+        Err(_) => {
+            warn!(
+                timeout_secs = limit.as_secs(),
+                "readiness check: migration history timed out"
+            );
+            return migrations_unknown("timed out");
+        }
+    };
+
+    let applied: HashSet<i64> = history
+        .iter()
+        .filter(|(_, success)| *success)
+        .map(|(version, _)| *version)
+        .collect();
+    let failed: Vec<i64> = history
+        .iter()
+        .filter(|(_, success)| !*success)
+        .map(|(version, _)| *version)
+        .collect();
+    let expected = expected_migrations();
+    let pending: Vec<i64> = expected
+        .iter()
+        .filter(|version| !applied.contains(version))
+        .copied()
+        .collect();
+
+    let status = if pending.is_empty() && failed.is_empty() {
+        HealthStatus::Up
+    } else {
+        HealthStatus::Down
+    };
+
+    MigrationCheck {
+        status,
+        expected: expected.len(),
+        applied: applied.len(),
+        pending,
+        failed,
+        error: None,
+    }
+}
+
+fn migrations_unknown(error: &'static str) -> MigrationCheck {
+    MigrationCheck {
+        status: HealthStatus::Down,
+        expected: expected_migrations().len(),
+        applied: 0,
+        pending: Vec::new(),
+        failed: Vec::new(),
+        error: Some(error),
+    }
+}
+
+fn expected_migrations() -> Vec<i64> {
+    MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .collect()
+}
+
+// Reported rather than failed on; see ReadinessChecks::status
+fn check_pool(db: &PgPool) -> PoolCheck {
+    PoolCheck {
+        size: db.size(),
+        idle: db.num_idle(),
+        max_connections: db.options().get_max_connections(),
+        saturated: pool_saturated(db),
     }
 }
@@ -0,0 +1,24 @@
+use crate::{errors::ApiError, metrics::observe_pool, state::AppState};
+use axum::{
+    extract::State,
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
+};
+use prometheus::{Encoder, TextEncoder};
+use tracing::error;
+
+// Prometheus text exposition; served on the metrics port only
+pub async fn metrics(State(state): State<AppState>) -> Result<Response, ApiError> {
+    observe_pool(&state.db);
+
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    encoder
+        .encode(&prometheus::gather(), &mut body)
+        .map_err(|err| {
+            error!(error = %err, "failed to encode metrics");
+            ApiError::internal()
+        })?;
+
+    Ok(([(CONTENT_TYPE, encoder.format_type().to_string())], body).into_response())
+}
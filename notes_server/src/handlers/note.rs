@@ -1,98 +1,561 @@
 use axum::{
-    Json,
-    extract::{Path, State},
-    http::StatusCode,
+    extract::State,
+    http::{
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+        header::{ETAG, IF_MATCH, IF_NONE_MATCH},
+    },
+    response::{IntoResponse, Response},
 };
+use services::models::{NoteCursor, NoteListFilter, NoteSearchCursor, NoteSearchFilter, Scope};
+use tracing::instrument;
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidateArgs};
 
 use crate::{
-    auth::middleware::RequireAuth,
+    auth::middleware::{RequireAuth, require_scope},
+    errors::{ApiError, ErrorEnvelope},
+    extractors::{Json, MultiQuery, Path, Query},
+    metrics::{NoteOperation, record_note_operation, record_note_operations},
     schemas::note_schemas::{
-        CreateNoteRequest, NoteData, NoteListResponse, NoteResponse, UpdateNoteRequest,
+        CreateNoteRequest, EmptyTrashResponse, ListNotesQuery, NoteData, NoteListResponse,
+        NoteResponse, NoteSearchResponse, RevisionData, RevisionDiffQuery, RevisionDiffResponse,
+        RevisionListResponse, RevisionResponse, SearchNotesQuery, UpdateNoteRequest, decode_cursor,
     },
     state::AppState,
 };
 
+#[utoipa::path(
+    post,
+    path = "/",
+    tag = "notes",
+    request_body = CreateNoteRequest,
+    responses(
+        (status = 200, description = "Note created", body = NoteResponse, headers(("ETag" = String, description = "Current version of the note"))),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Email not verified, or token lacks the notes:write scope", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
 pub async fn create_note(
-    RequireAuth(user): RequireAuth,
+    RequireAuth(user, credential): RequireAuth,
     State(state): State<AppState>,
     Json(payload): Json<CreateNoteRequest>,
-) -> Result<Json<NoteResponse>, StatusCode> {
-    payload
-        .note
-        .validate()
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
+) -> Result<Response, ApiError> {
+    require_scope(&credential, Scope::NotesWrite)?;
+
+    if state.require_verified_email && user.email_verified_at.is_none() {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "email_not_verified",
+            "Verify your email address before creating notes",
+        ));
+    }
+
+    payload.note.validate_with_args(&state.note_settings)?;
 
     let note = state
         .note_service
-        .create_note(user.id, &payload.note.title, &payload.note.content)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .create_note(
+            user.id,
+            &payload.note.title,
+            &payload.note.content,
+            &payload.note.tags,
+        )
+        .await?;
+
+    record_note_operation(NoteOperation::Created);
 
+    let etag = note_etag(note.version);
     let note_data = NoteData::from_note(note);
     let response = NoteResponse { note: note_data };
 
-    Ok(Json(response))
+    Ok(([(ETAG, etag)], Json(response)).into_response())
 }
 
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    tag = "notes",
+    params(("id" = Uuid, Path, description = "Note id"), ("If-None-Match" = Option<String>, Header, description = "ETags the client already has")),
+    responses(
+        (status = 200, description = "The note", body = NoteResponse, headers(("ETag" = String, description = "Current version of the note"))),
+        (status = 304, description = "The note still matches If-None-Match", headers(("ETag" = String, description = "Current version of the note"))),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the notes:read scope", body = ErrorEnvelope),
+        (status = 404, description = "Note not found", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all, fields(note_id = %note_id))]
 pub async fn find_note_by_id(
-    RequireAuth(user): RequireAuth,
+    RequireAuth(user, credential): RequireAuth,
     State(state): State<AppState>,
     Path(note_id): Path<Uuid>,
-) -> Result<Json<NoteResponse>, StatusCode> {
-    let Some(note) = state
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    require_scope(&credential, Scope::NotesRead)?;
+
+    let note = state
         .note_service
         .find_note_by_id(note_id, user.id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    else {
-        return Err(StatusCode::NOT_FOUND);
-    };
+        .await?
+        .ok_or_else(|| ApiError::not_found("Note not found"))?;
+
+    let etag = note_etag(note.version);
+
+    // If-None-Match uses the weak comparison, so W/ prefixed tags also match
+    if let Some(if_none_match) = header_str(&headers, IF_NONE_MATCH)? {
+        let matches = match parse_etag_list(if_none_match, true) {
+            None => true,
+            Some(versions) => versions.contains(&note.version),
+        };
+        if matches {
+            return Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response());
+        }
+    }
 
     let note_data = NoteData::from_note(note);
     let response = NoteResponse { note: note_data };
 
-    Ok(Json(response))
+    Ok(([(ETAG, etag)], Json(response)).into_response())
 }
 
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const INVALID_CURSOR_MESSAGE: &str = "Invalid pagination cursor";
+
+#[utoipa::path(
+    get,
+    path = "/me",
+    tag = "notes",
+    params(ListNotesQuery),
+    responses(
+        (status = 200, description = "A page of the caller's notes", body = NoteListResponse),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the notes:read scope", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
 pub async fn find_all_notes(
-    RequireAuth(user): RequireAuth,
+    RequireAuth(user, credential): RequireAuth,
     State(state): State<AppState>,
-) -> Result<Json<NoteListResponse>, StatusCode> {
-    let notes = state
-        .note_service
-        .find_notes_by_user_id(user.id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    MultiQuery(query): MultiQuery<ListNotesQuery>,
+) -> Result<Json<NoteListResponse>, ApiError> {
+    require_scope(&credential, Scope::NotesRead)?;
 
-    let note_list_response = NoteListResponse::from_notes(notes);
+    query.validate()?;
+
+    let cursor = match query.cursor.as_deref() {
+        Some(raw) => {
+            let cursor: NoteCursor =
+                decode_cursor(raw).ok_or_else(|| ApiError::bad_request(INVALID_CURSOR_MESSAGE))?;
+            if !cursor.matches(query.sort, query.direction) {
+                return Err(ApiError::bad_request(
+                    "Cursor does not match the requested sort order",
+                ));
+            }
+            Some(cursor)
+        }
+        None => None,
+    };
+
+    let filter = NoteListFilter {
+        user_id: user.id,
+        sort: query.sort,
+        direction: query.direction,
+        limit: query.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+        cursor,
+        created_after: query.created_after,
+        updated_before: query.updated_before,
+        tags: query.tags,
+        tag_match: query.tag_match,
+    };
+
+    let page = state.note_service.find_notes(filter).await?;
+
+    let note_list_response = NoteListResponse::from_page(page);
 
     Ok(Json(note_list_response))
 }
 
+#[utoipa::path(
+    get,
+    path = "/search",
+    tag = "notes",
+    params(SearchNotesQuery),
+    responses(
+        (status = 200, description = "Matching notes, best match first", body = NoteSearchResponse),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the notes:read scope", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn search_notes(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Query(query): Query<SearchNotesQuery>,
+) -> Result<Json<NoteSearchResponse>, ApiError> {
+    require_scope(&credential, Scope::NotesRead)?;
+
+    query.validate()?;
+
+    let cursor = match query.cursor.as_deref() {
+        Some(raw) => Some(
+            decode_cursor::<NoteSearchCursor>(raw)
+                .ok_or_else(|| ApiError::bad_request(INVALID_CURSOR_MESSAGE))?,
+        ),
+        None => None,
+    };
+
+    let filter = NoteSearchFilter {
+        user_id: user.id,
+        query: query.q,
+        limit: query.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+        cursor,
+    };
+
+    let page = state.note_service.search_notes(filter).await?;
+
+    Ok(Json(NoteSearchResponse::from_page(page)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/{id}",
+    tag = "notes",
+    params(("id" = Uuid, Path, description = "Note id"), ("If-Match" = Option<String>, Header, description = "Only update if the note still has one of these ETags")),
+    request_body = UpdateNoteRequest,
+    responses(
+        (status = 200, description = "Note updated", body = NoteResponse, headers(("ETag" = String, description = "Current version of the note"))),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the notes:write scope", body = ErrorEnvelope),
+        (status = 404, description = "Note not found", body = ErrorEnvelope),
+        (status = 412, description = "The note no longer matches If-Match", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all, fields(note_id = %note_id))]
 pub async fn update_note(
-    RequireAuth(user): RequireAuth,
+    RequireAuth(user, credential): RequireAuth,
     State(state): State<AppState>,
     Path(note_id): Path<Uuid>,
+    headers: HeaderMap,
     Json(payload): Json<UpdateNoteRequest>,
-) -> Result<Json<NoteResponse>, StatusCode> {
-    let Some(updated_note) = state
+) -> Result<Response, ApiError> {
+    require_scope(&credential, Scope::NotesWrite)?;
+
+    payload.note.validate_with_args(&state.note_settings)?;
+
+    // If-Match uses the strong comparison; `*` only requires the note to exist
+    let expected_versions =
+        header_str(&headers, IF_MATCH)?.and_then(|if_match| parse_etag_list(if_match, false));
+
+    let updated_note = state
         .note_service
         .update_note(
             note_id,
             user.id,
             payload.note.title.as_deref(),
             payload.note.content.as_deref(),
+            payload.note.tags.as_deref(),
+            expected_versions.as_deref(),
         )
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    else {
-        return Err(StatusCode::NOT_FOUND);
-    };
+        .await?;
+
+    record_note_operation(NoteOperation::Updated);
 
+    let etag = note_etag(updated_note.version);
     let note_data = NoteData::from_note(updated_note);
     let response = NoteResponse { note: note_data };
 
+    Ok(([(ETAG, etag)], Json(response)).into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/{id}",
+    tag = "notes",
+    params(("id" = Uuid, Path, description = "Note id")),
+    responses(
+        (status = 204, description = "Note moved to the trash"),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the notes:write scope", body = ErrorEnvelope),
+        (status = 404, description = "Note not found", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all, fields(note_id = %note_id))]
+pub async fn delete_note(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Path(note_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    require_scope(&credential, Scope::NotesWrite)?;
+
+    state
+        .note_service
+        .delete_note(note_id, user.id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Note not found"))?;
+
+    record_note_operation(NoteOperation::Deleted);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/trash",
+    tag = "notes",
+    responses(
+        (status = 200, description = "Notes in the trash", body = NoteListResponse),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the notes:read scope", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn find_deleted_notes(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+) -> Result<Json<NoteListResponse>, ApiError> {
+    require_scope(&credential, Scope::NotesRead)?;
+
+    let notes = state.note_service.find_deleted_notes(user.id).await?;
+
+    let note_list_response = NoteListResponse::from_notes(notes);
+
+    Ok(Json(note_list_response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/trash/{id}/restore",
+    tag = "notes",
+    params(("id" = Uuid, Path, description = "Note id")),
+    responses(
+        (status = 200, description = "Note restored from the trash", body = NoteResponse),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the notes:write scope", body = ErrorEnvelope),
+        (status = 404, description = "Note not found", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all, fields(note_id = %note_id))]
+pub async fn restore_note(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Path(note_id): Path<Uuid>,
+) -> Result<Json<NoteResponse>, ApiError> {
+    require_scope(&credential, Scope::NotesWrite)?;
+
+    let restored_note = state
+        .note_service
+        .restore_note(note_id, user.id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Note not found"))?;
+
+    record_note_operation(NoteOperation::Restored);
+
+    let note_data = NoteData::from_note(restored_note);
+    let response = NoteResponse { note: note_data };
+
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/trash",
+    tag = "notes",
+    responses(
+        (status = 200, description = "Trash emptied", body = EmptyTrashResponse),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the notes:write scope", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn empty_trash(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+) -> Result<Json<EmptyTrashResponse>, ApiError> {
+    require_scope(&credential, Scope::NotesWrite)?;
+
+    let deleted_count = state.note_service.empty_trash(user.id).await?;
+
+    record_note_operations(NoteOperation::Purged, deleted_count);
+
+    Ok(Json(EmptyTrashResponse { deleted_count }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/revisions",
+    tag = "notes",
+    params(("id" = Uuid, Path, description = "Note id")),
+    responses(
+        (status = 200, description = "Revisions of the note, newest first", body = RevisionListResponse),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the notes:read scope", body = ErrorEnvelope),
+        (status = 404, description = "Note not found", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all, fields(note_id = %note_id))]
+pub async fn find_revisions(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Path(note_id): Path<Uuid>,
+) -> Result<Json<RevisionListResponse>, ApiError> {
+    require_scope(&credential, Scope::NotesRead)?;
+
+    let revisions = state.note_service.find_revisions(note_id, user.id).await?;
+
+    if revisions.is_empty() {
+        return Err(ApiError::not_found("Note not found"));
+    }
+
+    Ok(Json(RevisionListResponse::from_revisions(revisions)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/revisions/{revision}",
+    tag = "notes",
+    params(("id" = Uuid, Path, description = "Note id"), ("revision" = i32, Path, description = "Revision number")),
+    responses(
+        (status = 200, description = "The revision", body = RevisionResponse),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the notes:read scope", body = ErrorEnvelope),
+        (status = 404, description = "Revision not found", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn find_revision(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Path((note_id, revision_number)): Path<(Uuid, i32)>,
+) -> Result<Json<RevisionResponse>, ApiError> {
+    require_scope(&credential, Scope::NotesRead)?;
+
+    let revision = state
+        .note_service
+        .find_revision(note_id, user.id, revision_number)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Revision not found"))?;
+
+    let response = RevisionResponse {
+        revision: RevisionData::from_revision(revision),
+    };
+
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/revisions/{revision}/restore",
+    tag = "notes",
+    params(("id" = Uuid, Path, description = "Note id"), ("revision" = i32, Path, description = "Revision number")),
+    responses(
+        (status = 200, description = "Note reverted to the revision", body = NoteResponse),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the notes:write scope", body = ErrorEnvelope),
+        (status = 404, description = "Revision not found", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn restore_revision(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Path((note_id, revision_number)): Path<(Uuid, i32)>,
+) -> Result<Json<NoteResponse>, ApiError> {
+    require_scope(&credential, Scope::NotesWrite)?;
+
+    let note = state
+        .note_service
+        .restore_revision(note_id, user.id, revision_number)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Revision not found"))?;
+
+    record_note_operation(NoteOperation::Reverted);
+
+    let note_data = NoteData::from_note(note);
+    let response = NoteResponse { note: note_data };
+
     Ok(Json(response))
 }
+
+#[utoipa::path(
+    get,
+    path = "/{id}/revisions/diff",
+    tag = "notes",
+    params(("id" = Uuid, Path, description = "Note id"), RevisionDiffQuery),
+    responses(
+        (status = 200, description = "Line diff between two revisions", body = RevisionDiffResponse),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the notes:read scope", body = ErrorEnvelope),
+        (status = 404, description = "Revision not found", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all, fields(note_id = %note_id))]
+pub async fn diff_revisions(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Path(note_id): Path<Uuid>,
+    Query(query): Query<RevisionDiffQuery>,
+) -> Result<Json<RevisionDiffResponse>, ApiError> {
+    require_scope(&credential, Scope::NotesRead)?;
+
+    let diff = state
+        .note_service
+        .diff_revisions(note_id, user.id, query.from, query.to)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Revision not found"))?;
+
+    Ok(Json(RevisionDiffResponse::from_diff(diff)))
+}
+
+fn note_etag(version: i32) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{}\"", version)).expect("version is a valid header value")
+}
+
+fn header_str(headers: &HeaderMap, name: HeaderName) -> Result<Option<&str>, ApiError> {
+    headers
+        .get(&name)
+        .map(|value| {
+            value
+                .to_str()
+                .map_err(|_| ApiError::bad_request(format!("Invalid {} header", name)))
+        })
+        .transpose()
+}
+
+// Parses an If-Match / If-None-Match header into note versions; `None` stands for `*`
+fn parse_etag_list(value: &str, weak: bool) -> Option<Vec<i32>> {
+    if value.trim() == "*" {
+        return None;
+    }
+
+    let versions = value
+        .split(',')
+        .map(str::trim)
+        .filter_map(|tag| match tag.strip_prefix("W/") {
+            Some(weak_tag) if weak => Some(weak_tag),
+            Some(_) => None,
+            None => Some(tag),
+        })
+        .filter_map(|tag| tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok())
+        .collect();
+
+    Some(versions)
+}
@@ -0,0 +1,74 @@
+use axum::extract::State;
+use services::models::Scope;
+use tracing::instrument;
+use validator::Validate;
+
+use crate::{
+    auth::middleware::{RequireAuth, require_scope},
+    errors::{ApiError, ErrorEnvelope},
+    extractors::{Json, Path},
+    schemas::tag_schemas::{RenameTagRequest, TagData, TagListResponse, TagResponse},
+    state::AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "tags",
+    responses(
+        (status = 200, description = "Tags with the number of notes using each", body = TagListResponse),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the notes:read scope", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn find_tags(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+) -> Result<Json<TagListResponse>, ApiError> {
+    require_scope(&credential, Scope::NotesRead)?;
+
+    let tags = state.tag_service.find_tags(user.id).await?;
+
+    Ok(Json(TagListResponse::from_tag_counts(tags)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/{name}",
+    tag = "tags",
+    params(("name" = String, Path, description = "Current tag name")),
+    request_body = RenameTagRequest,
+    responses(
+        (status = 200, description = "Tag renamed on every note", body = TagResponse),
+        (status = 400, description = "Invalid request", body = ErrorEnvelope),
+        (status = 401, description = "Missing, invalid or expired token", body = ErrorEnvelope),
+        (status = 403, description = "Token lacks the notes:write scope", body = ErrorEnvelope),
+        (status = 404, description = "Tag not found", body = ErrorEnvelope),
+    ),
+    security(("token" = []))
+)]
+#[instrument(skip_all)]
+pub async fn rename_tag(
+    RequireAuth(user, credential): RequireAuth,
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(payload): Json<RenameTagRequest>,
+) -> Result<Json<TagResponse>, ApiError> {
+    require_scope(&credential, Scope::NotesWrite)?;
+
+    payload.tag.validate()?;
+
+    let tag = state
+        .tag_service
+        .rename_tag(user.id, &name, &payload.tag.name)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Tag not found"))?;
+
+    let response = TagResponse {
+        tag: TagData::from_tag_count(tag),
+    };
+
+    Ok(Json(response))
+}
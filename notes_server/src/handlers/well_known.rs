@@ -0,0 +1,23 @@
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::State,
+    http::header::CACHE_CONTROL,
+    response::{IntoResponse, Response},
+};
+
+// Lets other services verify our access tokens without sharing a secret
+#[utoipa::path(
+    get,
+    path = "/jwks.json",
+    tag = "well-known",
+    responses(
+        (status = 200, description = "JSON Web Key Set for verifying access tokens", body = Object),
+    )
+)]
+pub async fn jwks(State(state): State<AppState>) -> Response {
+    let jwks = state.auth_service.jwks().clone();
+
+    // Short enough that a newly added key is picked up well before it starts signing
+    ([(CACHE_CONTROL, "public, max-age=300")], Json(jwks)).into_response()
+}
@@ -1,43 +1,257 @@
-use axum::Router;
-use std::env;
+use axum::{
+    http::{
+        HeaderName, HeaderValue, Method,
+        header::{AUTHORIZATION, CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH, RETRY_AFTER},
+    },
+    middleware,
+};
+use std::{net::SocketAddr, process::ExitCode, time::Duration};
+use tower_http::{
+    cors::{AllowOrigin, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+};
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::EnvFilter;
 
+mod access_log;
 mod auth;
+mod config;
+mod errors;
+mod extractors;
 mod handlers;
+mod metrics;
+mod openapi;
+mod rate_limit;
 mod routes;
 mod schemas;
+mod shutdown;
 mod state;
+mod tasks;
+use access_log::{REQUEST_ID_HEADER, access_log};
+use config::{Config, LogFormat};
+use metrics::track_http_metrics;
+use shutdown::{stop_accepting, watch_signals};
 use state::AppState;
 
-use crate::routes::{
-    auth_routes::auth_routes, health_routes::health_routes, note_routes::note_routes,
-    user_routes::user_routes,
+use crate::rate_limit::rate_limit;
+use crate::routes::{app_routes, metrics_routes::metrics_routes};
+use crate::tasks::{
+    login_attempt_purge::spawn_login_attempt_purge, trash_purge::spawn_trash_purge,
 };
 
+// Emails queued by the last requests get this long to go out
+const BACKGROUND_TASKS_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Closing the pool waits for checked-out connections, which a stuck request may never return
+const POOL_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     dotenvy::dotenv().ok();
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let filter = EnvFilter::new(&config.server.log_level);
+    match config.server.log_format {
+        LogFormat::Json => tracing_subscriber::fmt()
+            .json()
+            .with_env_filter(filter)
+            .init(),
+        LogFormat::Text => tracing_subscriber::fmt().with_env_filter(filter).init(),
+    }
+    debug!("Loaded configuration: {:?}", config);
+
+    let app_state = match AppState::new(&config).await {
+        Ok(app_state) => app_state,
+        Err(err) => {
+            error!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    info!("Connected to database successfully!");
+
+    metrics::init();
+
+    let shutdown = app_state.shutdown.clone();
+    let shutdown_delay = Duration::from_secs(config.server.shutdown_delay_secs);
+    let drain_timeout = Duration::from_secs(config.server.shutdown_drain_timeout_secs);
+    tokio::spawn(watch_signals(shutdown.clone()));
+
+    let trash_purge = spawn_trash_purge(
+        app_state.note_service.clone(),
+        chrono::Duration::days(config.notes.trash_retention_days),
+        Duration::from_secs(config.notes.trash_purge_interval_secs),
+        shutdown.clone(),
+    );
+
+    let login_attempt_purge =
+        spawn_login_attempt_purge(app_state.auth_service.clone(), shutdown.clone());
+
+    let mut app = app_routes(&config.notes).layer(middleware::from_fn_with_state(
+        app_state.clone(),
+        rate_limit,
+    ));
+
+    if let Some(cors) = cors_layer(&config.server.cors_origins) {
+        app = app.layer(cors);
+    }
+
+    // Callers may pass their own X-Request-Id; otherwise one is generated.
+    // Either way it is logged with the request and echoed on the response.
+    let request_id_header = HeaderName::from_static(REQUEST_ID_HEADER);
+    let app = app
+        .layer(middleware::from_fn(track_http_metrics))
+        .layer(middleware::from_fn(access_log))
+        .layer(PropagateRequestIdLayer::new(request_id_header.clone()))
+        .layer(SetRequestIdLayer::new(request_id_header, MakeRequestUuid));
 
-    let app_state = AppState::new(&database_url)
+    let app = app.with_state(app_state.clone());
+
+    // Metrics get their own listener so they can stay off the public interface
+    let metrics_app = metrics_routes().with_state(app_state.clone());
+    let metrics_listener =
+        match tokio::net::TcpListener::bind(config.server.metrics_bind_address).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!(
+                    "Cannot listen on {}: {}",
+                    config.server.metrics_bind_address, err
+                );
+                return ExitCode::FAILURE;
+            }
+        };
+    info!(
+        "Metrics available on http://{}/metrics",
+        config.server.metrics_bind_address
+    );
+    // Keeps answering scrapes until the main listener closes too
+    let metrics_server = tokio::spawn(
+        axum::serve(metrics_listener, metrics_app)
+            .with_graceful_shutdown(stop_accepting(shutdown.clone(), shutdown_delay))
+            .into_future(),
+    );
+
+    let listener = match tokio::net::TcpListener::bind(config.server.bind_address).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("Cannot listen on {}: {}", config.server.bind_address, err);
+            return ExitCode::FAILURE;
+        }
+    };
+    info!("Server running on http://{}", config.server.bind_address);
+
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(stop_accepting(shutdown.clone(), shutdown_delay));
+
+    // Each connection runs in its own task, so giving up on the server future doesn't stop
+    // requests still in flight; they only end when main returns and the runtime shuts down
+    let drain_deadline = async {
+        stop_accepting(shutdown.clone(), shutdown_delay).await;
+        info!("No longer accepting connections; draining in-flight requests");
+        tokio::time::sleep(drain_timeout).await;
+    };
+
+    let mut exit_code = ExitCode::SUCCESS;
+    tokio::select! {
+        result = server => {
+            if let Err(err) = result {
+                error!("Server error: {}", err);
+                exit_code = ExitCode::FAILURE;
+            }
+        }
+        _ = drain_deadline => {
+            warn!(
+                timeout_secs = drain_timeout.as_secs(),
+                "Drain timeout elapsed; dropping remaining requests"
+            );
+        }
+    }
+
+    // Background work is told to stop even if the server exited on its own
+    shutdown.cancel();
+    match metrics_server.await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => error!("Metrics server error: {}", err),
+        Err(err) => error!("Metrics server task failed: {}", err),
+    }
+    if let Err(err) = trash_purge.await {
+        error!("Trash purge task failed: {}", err);
+    }
+    if let Err(err) = login_attempt_purge.await {
+        error!("Login attempt purge task failed: {}", err);
+    }
+
+    if tokio::time::timeout(BACKGROUND_TASKS_TIMEOUT, app_state.background_tasks.wait())
         .await
-        .expect("Failed to connect to database");
-
-    println!("Connected to database successfully!");
-
-    let app = Router::new()
-        .nest(
-            "/api",
-            Router::new()
-                .nest("/health", health_routes())
-                .nest("/auth", auth_routes())
-                .nest("/users", user_routes())
-                .nest("/notes", note_routes()),
-        )
-        .with_state(app_state);
+        .is_err()
+    {
+        warn!(
+            timeout_secs = BACKGROUND_TASKS_TIMEOUT.as_secs(),
+            "Background tasks still running; dropping them"
+        );
+    }
+
+    match tokio::time::timeout(POOL_CLOSE_TIMEOUT, app_state.db.close()).await {
+        Ok(()) => info!("Database connections closed; shutdown complete"),
+        Err(_) => warn!(
+            timeout_secs = POOL_CLOSE_TIMEOUT.as_secs(),
+            "Database connections still in use; shutting down without closing them"
+        ),
+    }
+
+    exit_code
+}
+
+// Browsers may call the API from these origins; nothing is allowed when the list is empty
+fn cors_layer(origins: &[String]) -> Option<CorsLayer> {
+    if origins.is_empty() {
+        return None;
+    }
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    println!("Server running on http://localhost:3000");
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        )
+    };
 
-    axum::serve(listener, app).await.unwrap();
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .allow_headers([
+                AUTHORIZATION,
+                CONTENT_TYPE,
+                IF_MATCH,
+                IF_NONE_MATCH,
+                HeaderName::from_static(REQUEST_ID_HEADER),
+            ])
+            .expose_headers([
+                ETAG,
+                RETRY_AFTER,
+                HeaderName::from_static("x-ratelimit-limit"),
+                HeaderName::from_static("x-ratelimit-remaining"),
+                HeaderName::from_static("x-ratelimit-reset"),
+                HeaderName::from_static(REQUEST_ID_HEADER),
+            ]),
+    )
 }
@@ -0,0 +1,418 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use prometheus::{
+    HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, histogram_opts, opts,
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec,
+};
+use services::errors::AuthError;
+use sqlx::PgPool;
+use std::{sync::LazyLock, time::Instant};
+
+// Everything is registered with the prometheus default registry, which the
+// /metrics handler renders
+
+static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        opts!("http_requests_total", "HTTP requests handled"),
+        &["method", "route", "status"]
+    )
+    .expect("http_requests_total is registered once")
+});
+
+static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        histogram_opts!(
+            "http_request_duration_seconds",
+            "Time spent handling HTTP requests"
+        ),
+        &["method", "route", "status"]
+    )
+    .expect("http_request_duration_seconds is registered once")
+});
+
+static DB_POOL_CONNECTIONS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        opts!("db_pool_connections", "Open database connections"),
+        &["state"]
+    )
+    .expect("db_pool_connections is registered once")
+});
+
+static DB_POOL_MAX_CONNECTIONS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(opts!(
+        "db_pool_max_connections",
+        "Upper limit on database connections"
+    ))
+    .expect("db_pool_max_connections is registered once")
+});
+
+static DB_POOL_SATURATED: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(opts!(
+        "db_pool_saturated",
+        "1 while every database connection is open and checked out"
+    ))
+    .expect("db_pool_saturated is registered once")
+});
+
+// sqlx doesn't count waiters, so acquires that gave up waiting are the signal
+static DB_POOL_ACQUIRE_TIMEOUTS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(opts!(
+        "db_pool_acquire_timeouts_total",
+        "Requests that timed out waiting for a database connection"
+    ))
+    .expect("db_pool_acquire_timeouts_total is registered once")
+});
+
+static AUTH_LOGINS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        opts!("auth_logins_total", "Login attempts by step and outcome"),
+        &["step", "outcome"]
+    )
+    .expect("auth_logins_total is registered once")
+});
+
+static AUTH_TOKEN_VALIDATION_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(opts!(
+        "auth_token_validation_failures_total",
+        "Requests rejected because their access token was invalid or expired"
+    ))
+    .expect("auth_token_validation_failures_total is registered once")
+});
+
+static NOTE_OPERATIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        opts!(
+            "notes_operations_total",
+            "Notes created, updated, deleted, restored and permanently removed from the trash"
+        ),
+        &["operation"]
+    )
+    .expect("notes_operations_total is registered once")
+});
+
+#[derive(Debug, Clone, Copy)]
+pub enum LoginStep {
+    Password,
+    Mfa,
+}
+
+impl LoginStep {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LoginStep::Password => "password",
+            LoginStep::Mfa => "mfa",
+        }
+    }
+
+    // What each step can end in; only a password leads on to a second factor
+    fn results(&self) -> &'static [LoginResult] {
+        match self {
+            LoginStep::Password => &[
+                LoginResult::Success,
+                LoginResult::MfaRequired,
+                LoginResult::Failure,
+                LoginResult::Throttled,
+                LoginResult::Locked,
+                LoginResult::Error,
+            ],
+            LoginStep::Mfa => &[
+                LoginResult::Success,
+                LoginResult::Failure,
+                LoginResult::Throttled,
+                LoginResult::Locked,
+                LoginResult::Error,
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoginResult {
+    Success,
+    MfaRequired,
+    // Wrong credentials or code
+    Failure,
+    Throttled,
+    Locked,
+    // Something on our side went wrong
+    Error,
+}
+
+impl LoginResult {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LoginResult::Success => "success",
+            LoginResult::MfaRequired => "mfa_required",
+            LoginResult::Failure => "failure",
+            LoginResult::Throttled => "throttled",
+            LoginResult::Locked => "locked",
+            LoginResult::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum NoteOperation {
+    Created,
+    Updated,
+    Deleted,
+    // Taken back out of the trash
+    Restored,
+    // Rolled back to an earlier revision
+    Reverted,
+    // Permanently removed by emptying the trash
+    Purged,
+}
+
+impl NoteOperation {
+    const ALL: [NoteOperation; 6] = [
+        NoteOperation::Created,
+        NoteOperation::Updated,
+        NoteOperation::Deleted,
+        NoteOperation::Restored,
+        NoteOperation::Reverted,
+        NoteOperation::Purged,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            NoteOperation::Created => "created",
+            NoteOperation::Updated => "updated",
+            NoteOperation::Deleted => "deleted",
+            NoteOperation::Restored => "restored",
+            NoteOperation::Reverted => "reverted",
+            NoteOperation::Purged => "purged",
+        }
+    }
+}
+
+// Registers every metric up front so series exist at zero before anything happens
+pub fn init() {
+    LazyLock::force(&HTTP_REQUESTS);
+    LazyLock::force(&HTTP_REQUEST_DURATION);
+    LazyLock::force(&DB_POOL_MAX_CONNECTIONS);
+    LazyLock::force(&DB_POOL_SATURATED);
+    LazyLock::force(&DB_POOL_ACQUIRE_TIMEOUTS);
+    LazyLock::force(&AUTH_TOKEN_VALIDATION_FAILURES);
+
+    for state in ["idle", "in_use"] {
+        DB_POOL_CONNECTIONS.with_label_values(&[state]);
+    }
+    for step in [LoginStep::Password, LoginStep::Mfa] {
+        for result in step.results() {
+            AUTH_LOGINS.with_label_values(&[step.as_str(), result.as_str()]);
+        }
+    }
+    for operation in NoteOperation::ALL {
+        NOTE_OPERATIONS.with_label_values(&[operation.as_str()]);
+    }
+}
+
+pub async fn track_http_metrics(request: Request, next: Next) -> Response {
+    // Unmatched paths share one label so scanners can't blow up the series count
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+
+    let started_at = Instant::now();
+    let response = next.run(request).await;
+
+    let status = response.status().as_u16().to_string();
+    let labels = [method.as_str(), route.as_str(), status.as_str()];
+    HTTP_REQUESTS.with_label_values(&labels).inc();
+    HTTP_REQUEST_DURATION
+        .with_label_values(&labels)
+        .observe(started_at.elapsed().as_secs_f64());
+
+    response
+}
+
+// Pool gauges are sampled when scraped rather than tracked continuously
+pub fn observe_pool(db: &PgPool) {
+    let size = i64::from(db.size());
+    let idle = i64::try_from(db.num_idle()).unwrap_or(i64::MAX);
+
+    DB_POOL_CONNECTIONS.with_label_values(&["idle"]).set(idle);
+    DB_POOL_CONNECTIONS
+        .with_label_values(&["in_use"])
+        .set((size - idle).max(0));
+    DB_POOL_MAX_CONNECTIONS.set(i64::from(db.options().get_max_connections()));
+    DB_POOL_SATURATED.set(i64::from(pool_saturated(db)));
+}
+
+// Every connection is open and checked out, so new requests would queue for one
+pub fn pool_saturated(db: &PgPool) -> bool {
+    db.size() >= db.options().get_max_connections() && db.num_idle() == 0
+}
+
+pub fn record_pool_timeout() {
+    DB_POOL_ACQUIRE_TIMEOUTS.inc();
+}
+
+pub fn record_login(step: LoginStep, result: LoginResult) {
+    AUTH_LOGINS
+        .with_label_values(&[step.as_str(), result.as_str()])
+        .inc();
+}
+
+// Collapses auth errors into a handful of outcomes worth alerting on
+pub fn login_failure(err: &AuthError) -> LoginResult {
+    match err {
+        AuthError::TooManyAttempts { .. } => LoginResult::Throttled,
+        AuthError::AccountLocked { .. } => LoginResult::Locked,
+        AuthError::UserNotFound
+        | AuthError::InvalidPassword
+        | AuthError::InvalidMfaCode
+        | AuthError::TokenValidationError => LoginResult::Failure,
+        _ => LoginResult::Error,
+    }
+}
+
+pub fn record_token_validation_failure() {
+    AUTH_TOKEN_VALIDATION_FAILURES.inc();
+}
+
+pub fn record_note_operation(operation: NoteOperation) {
+    record_note_operations(operation, 1);
+}
+
+pub fn record_note_operations(operation: NoteOperation, count: u64) {
+    NOTE_OPERATIONS
+        .with_label_values(&[operation.as_str()])
+        .inc_by(count);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, http::StatusCode, middleware, routing::get};
+    use prometheus::proto::MetricFamily;
+    use std::collections::BTreeSet;
+    use tower::ServiceExt;
+
+    // Label values of every series, in the order `names` lists the labels
+    fn label_sets(family: &str, names: &[&str]) -> BTreeSet<Vec<String>> {
+        let families: Vec<MetricFamily> = prometheus::gather();
+        let family = families
+            .iter()
+            .find(|candidate| candidate.name() == family)
+            .unwrap_or_else(|| panic!("{family} is registered"));
+
+        family
+            .get_metric()
+            .iter()
+            .map(|metric| {
+                names
+                    .iter()
+                    .map(|name| {
+                        let label = metric
+                            .get_label()
+                            .iter()
+                            .find(|label| label.name() == *name);
+                        label
+                            .map(|label| label.value().to_string())
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn labels(values: &[&[&str]]) -> BTreeSet<Vec<String>> {
+        values
+            .iter()
+            .map(|labels| labels.iter().map(|label| label.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn login_series_exist_from_the_start() {
+        init();
+
+        assert_eq!(
+            label_sets("auth_logins_total", &["step", "outcome"]),
+            labels(&[
+                &["mfa", "error"],
+                &["mfa", "failure"],
+                &["mfa", "locked"],
+                &["mfa", "success"],
+                &["mfa", "throttled"],
+                &["password", "error"],
+                &["password", "failure"],
+                &["password", "locked"],
+                &["password", "mfa_required"],
+                &["password", "success"],
+                &["password", "throttled"],
+            ])
+        );
+    }
+
+    #[test]
+    fn note_operation_series_exist_from_the_start() {
+        init();
+
+        assert_eq!(
+            label_sets("notes_operations_total", &["operation"]),
+            labels(&[
+                &["created"],
+                &["deleted"],
+                &["purged"],
+                &["restored"],
+                &["reverted"],
+                &["updated"],
+            ])
+        );
+    }
+
+    #[test]
+    fn login_failures_map_to_results() {
+        assert_eq!(
+            login_failure(&AuthError::InvalidPassword),
+            LoginResult::Failure
+        );
+        assert_eq!(
+            login_failure(&AuthError::UserNotFound),
+            LoginResult::Failure
+        );
+        assert_eq!(
+            login_failure(&AuthError::SecretEncryptionError),
+            LoginResult::Error
+        );
+    }
+
+    #[tokio::test]
+    async fn unmatched_paths_share_one_route_label() {
+        let app = Router::new()
+            .route("/metrics-test/{id}", get(|| async { "ok" }))
+            .layer(middleware::from_fn(track_http_metrics));
+        let count = |route: &str, status: &str| {
+            HTTP_REQUESTS
+                .with_label_values(&["GET", route, status])
+                .get()
+        };
+        let unmatched_before = count("unmatched", "404");
+        let matched_before = count("/metrics-test/{id}", "200");
+
+        let request = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+        let response = app
+            .clone()
+            .oneshot(request("/no-such-route/1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app.oneshot(request("/metrics-test/42")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(count("unmatched", "404"), unmatched_before + 1);
+        // Matched requests are labelled with the route template, never the raw path
+        assert_eq!(count("/metrics-test/{id}", "200"), matched_before + 1);
+        assert_eq!(count("/metrics-test/42", "200"), 0);
+    }
+}
@@ -0,0 +1,189 @@
+use utoipa::{
+    Modify, OpenApi,
+    openapi::{
+        self, RefOr, Schema,
+        schema::Object,
+        security::{ApiKey, ApiKeyValue, SecurityScheme},
+    },
+};
+
+use services::models::{NoteSortField, SortDirection, TagMatch};
+
+use crate::config::NoteSettings;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Notes API",
+        description = "Errors are returned as `{\"error\": {\"code\", \"message\", \"fields\"?}}`. \
+            Every route is rate limited; a 429 response carries a Retry-After header."
+    ),
+    // Query parameters refer to these but, unlike bodies, don't register them
+    components(schemas(NoteSortField, SortDirection, TagMatch)),
+    modifiers(&TokenAuth),
+    tags(
+        (name = "auth", description = "Registration, sign-in, sessions and two-factor auth"),
+        (name = "users", description = "The signed-in user's profile"),
+        (name = "notes", description = "Notes, the trash and revision history"),
+        (name = "tags", description = "Tags across the caller's notes"),
+        (name = "health", description = "Liveness and readiness probes"),
+        (name = "well-known", description = "Keys for verifying access tokens"),
+    )
+)]
+pub struct ApiDoc;
+
+struct TokenAuth;
+
+impl Modify for TokenAuth {
+    fn modify(&self, openapi: &mut openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "token",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                "Authorization",
+                "`Token <access token>` or `Token <personal access token>`",
+            ))),
+        );
+    }
+}
+
+// The note limits are configurable, so they are filled in from the running config
+pub fn document(mut openapi: openapi::OpenApi, settings: &NoteSettings) -> openapi::OpenApi {
+    for schema in ["CreateNoteData", "UpdateNoteData"] {
+        for (property, max_length) in [
+            ("title", settings.max_title_length),
+            ("content", settings.max_content_length),
+        ] {
+            if let Some(property) = property_mut(&mut openapi, schema, property) {
+                property.max_length = Some(max_length);
+            }
+        }
+    }
+
+    openapi
+}
+
+fn property_mut<'a>(
+    openapi: &'a mut openapi::OpenApi,
+    schema: &str,
+    property: &str,
+) -> Option<&'a mut Object> {
+    let RefOr::T(Schema::Object(object)) = openapi.components.as_mut()?.schemas.get_mut(schema)?
+    else {
+        return None;
+    };
+
+    match object.properties.get_mut(property)? {
+        RefOr::T(Schema::Object(property)) => Some(property),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::api_routes;
+    use serde_json::Value;
+
+    fn collected() -> openapi::OpenApi {
+        document(api_routes().into_openapi(), &NoteSettings::default())
+    }
+
+    #[test]
+    fn paths_carry_their_mount_prefix() {
+        let openapi = collected();
+        let tags: Vec<_> = openapi
+            .tags
+            .iter()
+            .flatten()
+            .map(|tag| tag.name.clone())
+            .collect();
+
+        assert!(!openapi.paths.paths.is_empty());
+        for (path, item) in &openapi.paths.paths {
+            assert!(
+                path.starts_with("/api/") || path.starts_with("/.well-known/"),
+                "{} is not nested under its router's prefix",
+                path
+            );
+            assert!(!path.ends_with('/'), "{} has a trailing slash", path);
+
+            let operations = [&item.get, &item.post, &item.put, &item.patch, &item.delete];
+            for operation in operations.into_iter().flatten() {
+                let operation_tags = operation.tags.as_deref().unwrap_or_default();
+                assert!(
+                    !operation_tags.is_empty()
+                        && operation_tags.iter().all(|tag| tags.contains(tag)),
+                    "{} is tagged {:?}, expected one of {:?}",
+                    path,
+                    operation_tags,
+                    tags
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn health_alias_is_not_documented() {
+        let openapi = collected();
+
+        assert!(openapi.paths.paths.contains_key("/api/health/ready"));
+        assert!(!openapi.paths.paths.contains_key("/api/health"));
+    }
+
+    #[test]
+    fn every_schema_reference_resolves() {
+        let openapi = serde_json::to_value(collected()).expect("document serializes");
+        let schemas = &openapi["components"]["schemas"];
+
+        let mut references = Vec::new();
+        collect_references(&openapi, &mut references);
+
+        assert!(!references.is_empty());
+        for reference in references {
+            let name = reference
+                .strip_prefix("#/components/schemas/")
+                .unwrap_or_else(|| panic!("unexpected reference {}", reference));
+            assert!(
+                schemas.get(name).is_some(),
+                "{} is referenced but not a component",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn note_limits_follow_settings() {
+        let mut openapi = collected();
+
+        for schema in ["CreateNoteData", "UpdateNoteData"] {
+            for (property, max_length) in [("title", 50), ("content", 500)] {
+                let max = property_mut(&mut openapi, schema, property)
+                    .unwrap_or_else(|| panic!("{}.{} is not in the document", schema, property))
+                    .max_length;
+                assert_eq!(max, Some(max_length), "{}.{} maxLength", schema, property);
+            }
+        }
+    }
+
+    fn collect_references(value: &Value, references: &mut Vec<String>) {
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    match value {
+                        Value::String(reference) if key == "$ref" => {
+                            references.push(reference.clone())
+                        }
+                        _ => collect_references(value, references),
+                    }
+                }
+            }
+            Value::Array(values) => {
+                for value in values {
+                    collect_references(value, references);
+                }
+            }
+            _ => {}
+        }
+    }
+}
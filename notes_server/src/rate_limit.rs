@@ -0,0 +1,170 @@
+use axum::{
+    extract::{Request, State},
+    http::{Extensions, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ipnet::Ipv6Net;
+use services::rate_limiter::{RateLimit, RateLimitDecision};
+use std::net::IpAddr;
+use tracing::error;
+
+use crate::{
+    auth::middleware::extract_token_from_headers, errors::ApiError, extractors::client_ip,
+    state::AppState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitGroup {
+    Auth,
+    Write,
+    Read,
+}
+
+impl RateLimitGroup {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitGroup::Auth => "auth",
+            RateLimitGroup::Write => "write",
+            RateLimitGroup::Read => "read",
+        }
+    }
+
+    // Health checks are never limited so probes can't be locked out
+    fn classify(method: &Method, path: &str) -> Option<Self> {
+        if path.starts_with("/api/health") {
+            return None;
+        }
+        if path.starts_with("/api/auth") {
+            return Some(RateLimitGroup::Auth);
+        }
+
+        match *method {
+            Method::GET | Method::HEAD | Method::OPTIONS => Some(RateLimitGroup::Read),
+            _ => Some(RateLimitGroup::Write),
+        }
+    }
+}
+
+// A capacity of zero turns limiting off for that group
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimits {
+    pub auth: RateLimit,
+    pub write: RateLimit,
+    pub read: RateLimit,
+}
+
+impl RateLimits {
+    fn for_group(&self, group: RateLimitGroup) -> RateLimit {
+        match group {
+            RateLimitGroup::Auth => self.auth,
+            RateLimitGroup::Write => self.write,
+            RateLimitGroup::Read => self.read,
+        }
+    }
+}
+
+// Buckets are keyed by the user a validly signed session token names and by
+// client IP otherwise. Nothing here touches the database: personal access
+// tokens and revoked sessions are left for the auth middleware to reject.
+pub async fn rate_limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(group) = RateLimitGroup::classify(request.method(), request.uri().path()) else {
+        return next.run(request).await;
+    };
+
+    let limit = state.rate_limits.for_group(group);
+    if limit.capacity == 0 {
+        return next.run(request).await;
+    }
+
+    let client = client_key(&state, request.headers(), request.extensions());
+    let key = format!("{}:{}", group.as_str(), client);
+
+    // A broken store shouldn't take the API down with it
+    let decision = match state.rate_limit_store.acquire(&key, limit).await {
+        Ok(decision) => decision,
+        Err(err) => {
+            error!(error = %err, "rate limit store failed");
+            return next.run(request).await;
+        }
+    };
+
+    let mut response = match decision.retry_after {
+        None => next.run(request).await,
+        Some(retry_after) => ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "rate_limited",
+            "Too many requests; please slow down",
+        )
+        .with_retry_after(chrono::Duration::from_std(retry_after).unwrap_or_default())
+        .into_response(),
+    };
+
+    set_rate_limit_headers(response.headers_mut(), &decision);
+
+    response
+}
+
+fn client_key(state: &AppState, headers: &HeaderMap, extensions: &Extensions) -> String {
+    if let Some(token) = extract_token_from_headers(headers)
+        && let Some(user_id) = state.auth_service.token_subject(&token)
+    {
+        return format!("user:{}", user_id);
+    }
+
+    match client_ip(headers, extensions, &state.trusted_proxies) {
+        Some(ip) => ip_key(ip),
+        None => "ip:unknown".to_string(),
+    }
+}
+
+// An IPv6 client usually holds a whole /64, so one address per request would
+// never run out of buckets; the prefix is what identifies it
+fn ip_key(ip: IpAddr) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => format!("ip:{}", ip),
+        IpAddr::V6(ip) => match Ipv6Net::new(ip, 64) {
+            Ok(net) => format!("ip:{}", net.trunc()),
+            Err(_) => format!("ip:{}", ip),
+        },
+    }
+}
+
+fn set_rate_limit_headers(headers: &mut HeaderMap, decision: &RateLimitDecision) {
+    headers.insert("X-RateLimit-Limit", HeaderValue::from(decision.limit));
+    headers.insert(
+        "X-RateLimit-Remaining",
+        HeaderValue::from(decision.remaining),
+    );
+    headers.insert(
+        "X-RateLimit-Reset",
+        HeaderValue::from(decision.reset_after.as_secs_f64().ceil() as u64),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(ip: &str) -> String {
+        ip_key(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn ipv4_clients_are_keyed_by_address() {
+        assert_eq!(key("203.0.113.7"), "ip:203.0.113.7");
+        assert_ne!(key("203.0.113.7"), key("203.0.113.8"));
+    }
+
+    #[test]
+    fn ipv6_clients_are_keyed_by_their_64_prefix() {
+        assert_eq!(key("2001:db8:1:2:aaaa::1"), "ip:2001:db8:1:2::/64");
+        assert_eq!(key("2001:db8:1:2:aaaa::1"), key("2001:db8:1:2:bbbb::9"));
+        assert_ne!(key("2001:db8:1:2::1"), key("2001:db8:1:3::1"));
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_are_keyed_as_ipv4() {
+        assert_eq!(key("::ffff:203.0.113.7"), "ip:203.0.113.7");
+    }
+}
@@ -1,4 +1,43 @@
 pub mod auth_routes;
+pub mod docs_routes;
 pub mod health_routes;
+pub mod metrics_routes;
 pub mod note_routes;
+pub mod tag_routes;
 pub mod user_routes;
+pub mod well_known_routes;
+
+use axum::Router;
+use utoipa::OpenApi;
+use utoipa_axum::router::OpenApiRouter;
+
+use crate::{
+    config::NoteSettings,
+    openapi::{self, ApiDoc},
+    state::AppState,
+};
+
+// Everything served on the main listener; /metrics has a listener of its own
+pub fn app_routes(note_settings: &NoteSettings) -> Router<AppState> {
+    let (router, api) = api_routes().split_for_parts();
+    router.merge(docs_routes::docs_routes(openapi::document(
+        api,
+        note_settings,
+    )))
+}
+
+// Handlers are registered together with their #[utoipa::path] docs, so the
+// OpenAPI document lists exactly the routes served here
+pub fn api_routes() -> OpenApiRouter<AppState> {
+    OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .nest(
+            "/api",
+            OpenApiRouter::new()
+                .nest("/health", health_routes::health_routes())
+                .nest("/auth", auth_routes::auth_routes())
+                .nest("/users", user_routes::user_routes())
+                .nest("/notes", note_routes::note_routes())
+                .nest("/tags", tag_routes::tag_routes()),
+        )
+        .nest("/.well-known", well_known_routes::well_known_routes())
+}
@@ -1,12 +1,26 @@
-use axum::{Router, routing::post};
+use utoipa_axum::{router::OpenApiRouter, routes};
 
-use crate::{
-    handlers::auth::{login, register},
-    state::AppState,
-};
+use crate::{handlers::auth, state::AppState};
 
-pub fn auth_routes() -> Router<AppState> {
-    Router::new()
-        .route("/register", post(register))
-        .route("/login", post(login))
+pub fn auth_routes() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(auth::register))
+        .routes(routes!(auth::login))
+        .routes(routes!(auth::refresh))
+        .routes(routes!(auth::verify_mfa))
+        .routes(routes!(auth::enroll_totp))
+        .routes(routes!(auth::confirm_totp))
+        .routes(routes!(auth::disable_totp))
+        .routes(routes!(auth::regenerate_recovery_codes))
+        .routes(routes!(auth::request_password_reset))
+        .routes(routes!(auth::confirm_password_reset))
+        .routes(routes!(auth::verify_email))
+        .routes(routes!(auth::resend_verification_email))
+        .routes(routes!(auth::logout))
+        .routes(routes!(auth::logout_all))
+        .routes(routes!(auth::find_sessions))
+        .routes(routes!(auth::revoke_session))
+        .routes(routes!(auth::create_personal_access_token))
+        .routes(routes!(auth::find_personal_access_tokens))
+        .routes(routes!(auth::revoke_personal_access_token))
 }
@@ -0,0 +1,10 @@
+use axum::Router;
+use utoipa::openapi::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::state::AppState;
+
+// Mounted at the root rather than nested: the UI redirects to absolute paths
+pub fn docs_routes(openapi: OpenApi) -> Router<AppState> {
+    Router::new().merge(SwaggerUi::new("/api/docs").url("/api/openapi.json", openapi))
+}
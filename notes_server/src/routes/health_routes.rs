@@ -1,7 +1,12 @@
-use axum::{Router, routing::get};
+use axum::routing::get;
+use utoipa_axum::{router::OpenApiRouter, routes};
 
-use crate::{handlers::health::health_check, state::AppState};
+use crate::{handlers::health, state::AppState};
 
-pub fn health_routes() -> Router<AppState> {
-    Router::new().route("/", get(health_check))
+pub fn health_routes() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(health::liveness))
+        .routes(routes!(health::readiness))
+        // Kept for probes configured before the split
+        .route("/", get(health::readiness))
 }
@@ -0,0 +1,12 @@
+use axum::{Router, routing::get};
+
+use crate::{
+    handlers::{health::readiness_details, metrics::metrics},
+    state::AppState,
+};
+
+pub fn metrics_routes() -> Router<AppState> {
+    Router::new()
+        .route("/metrics", get(metrics))
+        .route("/health/ready", get(readiness_details))
+}
@@ -1,17 +1,20 @@
-use axum::{
-    Router,
-    routing::{get, patch, post},
-};
+use utoipa_axum::{router::OpenApiRouter, routes};
 
-use crate::{
-    handlers::note::{create_note, find_all_notes, find_note_by_id, update_note},
-    state::AppState,
-};
+use crate::{handlers::note, state::AppState};
 
-pub fn note_routes() -> Router<AppState> {
-    Router::new()
-        .route("/", post(create_note))
-        .route("/{id}", get(find_note_by_id))
-        .route("/me", get(find_all_notes))
-        .route("/{id}", patch(update_note))
+pub fn note_routes() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(note::create_note))
+        .routes(routes!(note::find_note_by_id))
+        .routes(routes!(note::find_all_notes))
+        .routes(routes!(note::search_notes))
+        .routes(routes!(note::update_note))
+        .routes(routes!(note::delete_note))
+        .routes(routes!(note::find_deleted_notes))
+        .routes(routes!(note::empty_trash))
+        .routes(routes!(note::restore_note))
+        .routes(routes!(note::find_revisions))
+        .routes(routes!(note::diff_revisions))
+        .routes(routes!(note::find_revision))
+        .routes(routes!(note::restore_revision))
 }
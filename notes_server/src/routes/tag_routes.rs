@@ -0,0 +1,9 @@
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{handlers::tag, state::AppState};
+
+pub fn tag_routes() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(tag::find_tags))
+        .routes(routes!(tag::rename_tag))
+}
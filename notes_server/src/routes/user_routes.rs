@@ -1,7 +1,10 @@
-use axum::{Router, routing::get};
+use utoipa_axum::{router::OpenApiRouter, routes};
 
-use crate::{handlers::auth::current_user, state::AppState};
+use crate::{handlers::auth, state::AppState};
 
-pub fn user_routes() -> Router<AppState> {
-    Router::new().route("/user", get(current_user))
+pub fn user_routes() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(auth::current_user))
+        .routes(routes!(auth::update_current_user))
+        .routes(routes!(auth::change_password))
 }
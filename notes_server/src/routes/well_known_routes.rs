@@ -0,0 +1,7 @@
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{handlers::well_known, state::AppState};
+
+pub fn well_known_routes() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new().routes(routes!(well_known::jwks))
+}
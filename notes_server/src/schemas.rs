@@ -1,2 +1,4 @@
 pub mod auth_schemas;
+pub mod health_schemas;
 pub mod note_schemas;
+pub mod tag_schemas;
@@ -1,64 +1,359 @@
-use serde::{Deserialize, Serialize};
-use services::User;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use services::{
+    User,
+    models::{AuthTokens, PersonalAccessToken, Scope, Session, TotpEnrollment},
+};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
 use validator::Validate;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct RegisterUserRequest {
     pub user: RegisterUserData,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct RegisterUserData {
     #[validate(length(
         min = 3,
         max = 50,
         message = "Username must be between 3 and 50 characters"
     ))]
+    #[schema(min_length = 3, max_length = 50)]
     pub username: String,
 
     #[validate(email(message = "Invalid email format"))]
+    #[schema(format = Email)]
     pub email: String,
 
     #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
+    #[schema(min_length = 8, format = Password)]
     pub password: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct LoginUserRequest {
     pub user: LoginUserData,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct LoginUserData {
     #[validate(email(message = "Invalid email format"))]
+    #[schema(format = Email)]
     pub email: String,
 
     #[validate(length(min = 1, message = "Password is required"))]
+    #[schema(min_length = 1, format = Password)]
     pub password: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct RefreshTokenRequest {
+    #[validate(length(min = 1, message = "Refresh token is required"))]
+    #[schema(min_length = 1)]
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateUserRequest {
+    pub user: UpdateUserData,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdateUserData {
+    #[validate(length(
+        min = 3,
+        max = 50,
+        message = "Username must be between 3 and 50 characters"
+    ))]
+    #[schema(min_length = 3, max_length = 50)]
+    pub username: Option<String>,
+
+    #[validate(email(message = "Invalid email format"))]
+    #[schema(format = Email)]
+    pub email: Option<String>,
+
+    // Required with `email`, since the address is where password resets go
+    #[schema(format = Password)]
+    pub current_password: Option<String>,
+
+    // null clears the bio, leaving the field out keeps it
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    #[validate(length(max = 1000, message = "Bio must be at most 1000 characters"))]
+    #[schema(value_type = Option<String>, max_length = 1000)]
+    pub bio: Option<Option<String>>,
+
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    #[validate(
+        url(message = "Image must be a valid URL"),
+        length(max = 255, message = "Image URL must be at most 255 characters")
+    )]
+    #[schema(value_type = Option<String>, format = Uri, max_length = 255)]
+    pub image: Option<Option<String>>,
+}
+
+// Tells an explicit null (`Some(None)`) apart from a missing field (`None`)
+fn deserialize_nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ChangePasswordRequest {
+    #[validate(length(min = 1, message = "Current password is required"))]
+    #[schema(min_length = 1, format = Password)]
+    pub current_password: String,
+
+    #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
+    #[schema(min_length = 8, format = Password)]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct PasswordResetRequest {
+    #[validate(email(message = "Invalid email format"))]
+    #[schema(format = Email)]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct PasswordResetConfirmRequest {
+    #[validate(length(min = 1, message = "Reset token is required"))]
+    #[schema(min_length = 1)]
+    pub token: String,
+
+    #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
+    #[schema(min_length = 8, format = Password)]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct MfaVerifyRequest {
+    #[validate(length(min = 1, message = "MFA token is required"))]
+    #[schema(min_length = 1)]
+    pub mfa_token: String,
+
+    #[validate(length(min = 1, message = "Code is required"))]
+    #[schema(min_length = 1)]
+    pub code: String,
+}
+
+// Used to confirm enrollment and to authorise changes to two-factor settings
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct MfaCodeRequest {
+    #[validate(length(min = 1, message = "Code is required"))]
+    #[schema(min_length = 1)]
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct VerifyEmailQuery {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct UserResponse {
     pub user: UserData,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct UserData {
     pub email: String,
-    pub token: String,
+    pub email_verified: bool,
+    // Absent when the caller authenticated with a personal access token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
     pub username: String,
     pub bio: String,
     pub image: Option<String>,
 }
 
 impl UserData {
-    pub fn from_user_with_token(user: User, token: String) -> Self {
+    pub fn from_user_with_token(user: User, token: Option<String>) -> Self {
         Self {
             email: user.email,
+            email_verified: user.email_verified_at.is_some(),
             token,
+            refresh_token: None,
             username: user.username,
             bio: user.bio.unwrap_or_default(), // Empty string if None
             image: user.image,                 // Keep as Option<String>
         }
     }
+
+    pub fn from_user_with_tokens(user: User, tokens: AuthTokens) -> Self {
+        Self {
+            refresh_token: Some(tokens.refresh_token),
+            ..Self::from_user_with_token(user, Some(tokens.access_token))
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authenticated(UserResponse),
+    MfaRequired(MfaChallengeResponse),
+}
+
+// Returned by login instead of tokens when the account has two-factor auth enabled
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MfaChallengeResponse {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+}
+
+impl MfaChallengeResponse {
+    pub fn new(mfa_token: String) -> Self {
+        Self {
+            mfa_required: true,
+            mfa_token,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TotpEnrollmentResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+impl From<TotpEnrollment> for TotpEnrollmentResponse {
+    fn from(enrollment: TotpEnrollment) -> Self {
+        Self {
+            secret: enrollment.secret,
+            otpauth_uri: enrollment.otpauth_uri,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionData {
+    pub session_id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub current: bool,
+}
+
+impl SessionData {
+    pub fn from_session(session: Session, current_session_id: Option<Uuid>) -> Self {
+        Self {
+            current: current_session_id == Some(session.id),
+            session_id: session.id,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            created_at: session.created_at,
+            last_used_at: session.last_used_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionListResponse {
+    pub sessions: Vec<SessionData>,
+}
+
+impl SessionListResponse {
+    pub fn from_sessions(sessions: Vec<Session>, current_session_id: Option<Uuid>) -> Self {
+        Self {
+            sessions: sessions
+                .into_iter()
+                .map(|session| SessionData::from_session(session, current_session_id))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreatePersonalAccessTokenRequest {
+    pub token: CreatePersonalAccessTokenData,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreatePersonalAccessTokenData {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Token name must be between 1 and 100 characters"
+    ))]
+    #[schema(min_length = 1, max_length = 100)]
+    pub name: String,
+
+    #[validate(length(min = 1, message = "At least one scope is required"))]
+    #[schema(min_items = 1)]
+    pub scopes: Vec<Scope>,
+
+    #[validate(range(min = 1, max = 365, message = "Expiry must be between 1 and 365 days"))]
+    #[schema(minimum = 1, maximum = 365)]
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PersonalAccessTokenData {
+    pub token_id: Uuid,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl PersonalAccessTokenData {
+    pub fn from_token(token: PersonalAccessToken) -> Self {
+        Self {
+            scopes: token.scopes(),
+            token_id: token.id,
+            name: token.name,
+            token_prefix: token.token_prefix,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+            created_at: token.created_at,
+            token: None,
+        }
+    }
+
+    pub fn from_token_with_secret(token: PersonalAccessToken, secret: String) -> Self {
+        Self {
+            token: Some(secret),
+            ..Self::from_token(token)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PersonalAccessTokenResponse {
+    pub token: PersonalAccessTokenData,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PersonalAccessTokenListResponse {
+    pub tokens: Vec<PersonalAccessTokenData>,
+}
+
+impl PersonalAccessTokenListResponse {
+    pub fn from_tokens(tokens: Vec<PersonalAccessToken>) -> Self {
+        Self {
+            tokens: tokens
+                .into_iter()
+                .map(PersonalAccessTokenData::from_token)
+                .collect(),
+        }
+    }
 }
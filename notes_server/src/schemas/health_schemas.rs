@@ -0,0 +1,92 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    // Set from GIT_COMMIT at compile time when the build provides it
+    pub commit: Option<&'static str>,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            commit: option_env!("GIT_COMMIT").filter(|commit| !commit.is_empty()),
+        }
+    }
+}
+
+// All the public probes answer with; the details are on the metrics port
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthResponse {
+    pub status: HealthStatus,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    pub status: HealthStatus,
+    pub build: BuildInfo,
+    // Set once a shutdown signal arrives; the checks below may still pass
+    pub shutting_down: bool,
+    pub checks: ReadinessChecks,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessChecks {
+    pub database: DatabaseCheck,
+    pub migrations: MigrationCheck,
+    pub pool: PoolCheck,
+}
+
+impl ReadinessChecks {
+    pub fn status(&self) -> HealthStatus {
+        // A saturated pool still serves requests, only slower; pulling every
+        // instance out of rotation at once would turn load into an outage
+        let statuses = [self.database.status, self.migrations.status];
+
+        if statuses.contains(&HealthStatus::Down) {
+            HealthStatus::Down
+        } else {
+            HealthStatus::Up
+        }
+    }
+}
+
+// Errors are short fixed descriptions; the underlying cause only goes to the logs
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DatabaseCheck {
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MigrationCheck {
+    pub status: HealthStatus,
+    pub expected: usize,
+    pub applied: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pending: Vec<i64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed: Vec<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PoolCheck {
+    pub size: u32,
+    pub idle: usize,
+    pub max_connections: u32,
+    pub saturated: bool,
+}
@@ -1,47 +1,114 @@
-use serde::{Deserialize, Serialize};
-use services::Note;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use services::{
+    Note,
+    models::{
+        DiffLine, NotePage, NoteRevision, NoteSearchHit, NoteSearchPage, NoteSortField,
+        RevisionDiff, SortDirection, TagMatch,
+    },
+};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
-use validator::Validate;
 
-#[derive(Debug, Deserialize)]
+use crate::{config::NoteSettings, schemas::tag_schemas::validate_tag};
+use validator::{Validate, ValidationError};
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateNoteRequest {
     pub note: CreateNoteData,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+// Title and content limits come from the `[notes]` config section
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+#[validate(context = NoteSettings)]
 pub struct CreateNoteData {
-    #[validate(length(max = 50, message = "Title cannot exceed 50 characters"))]
+    #[validate(custom(function = "validate_title", use_context))]
     pub title: String,
 
-    #[validate(length(max = 500, message = "Content cannot exceed 500 characters"))]
+    #[validate(custom(function = "validate_content", use_context))]
     pub content: String,
+
+    #[serde(default)]
+    #[validate(custom(function = "validate_tags"))]
+    #[schema(max_items = 20)]
+    pub tags: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct UpdateNoteRequest {
     pub note: UpdateNoteData,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+#[validate(context = NoteSettings)]
 pub struct UpdateNoteData {
-    #[validate(length(max = 50, message = "Title cannot exceed 50 characters"))]
+    #[validate(custom(function = "validate_title", use_context))]
     pub title: Option<String>,
 
-    #[validate(length(max = 500, message = "Content cannot exceed 500 characters"))]
+    #[validate(custom(function = "validate_content", use_context))]
     pub content: Option<String>,
+
+    #[validate(custom(function = "validate_tags"))]
+    #[schema(max_items = 20)]
+    pub tags: Option<Vec<String>>,
+}
+
+pub fn validate_title(title: &str, settings: &NoteSettings) -> Result<(), ValidationError> {
+    if title.chars().count() > settings.max_title_length {
+        return Err(ValidationError::new("length").with_message(
+            format!(
+                "Title cannot exceed {} characters",
+                settings.max_title_length
+            )
+            .into(),
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn validate_content(content: &str, settings: &NoteSettings) -> Result<(), ValidationError> {
+    if content.chars().count() > settings.max_content_length {
+        return Err(ValidationError::new("length").with_message(
+            format!(
+                "Content cannot exceed {} characters",
+                settings.max_content_length
+            )
+            .into(),
+        ));
+    }
+
+    Ok(())
 }
 
-#[derive(Debug, Serialize)]
+const MAX_TAGS_PER_NOTE: usize = 20;
+
+pub fn validate_tags(tags: &[String]) -> Result<(), ValidationError> {
+    if tags.len() > MAX_TAGS_PER_NOTE {
+        return Err(ValidationError::new("tags")
+            .with_message("A note cannot have more than 20 tags".into()));
+    }
+
+    tags.iter().try_for_each(|tag| validate_tag(tag))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct NoteResponse {
     pub note: NoteData,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct NoteData {
     pub note_id: Uuid,
     pub user_id: Uuid,
     pub title: String,
     pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
 }
 
 impl NoteData {
@@ -51,19 +118,203 @@ impl NoteData {
             user_id: note.user_id,
             title: note.title,
             content: note.content,
+            created_at: note.created_at,
+            updated_at: note.updated_at,
+            deleted_at: note.deleted_at,
+            tags: note.tags,
         }
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListNotesQuery {
+    pub cursor: Option<String>,
+
+    #[validate(range(min = 1, max = 100, message = "Limit must be between 1 and 100"))]
+    #[param(minimum = 1, maximum = 100)]
+    pub limit: Option<i64>,
+
+    #[serde(default)]
+    pub sort: NoteSortField,
+
+    #[serde(default)]
+    pub direction: SortDirection,
+
+    pub created_after: Option<DateTime<Utc>>,
+    pub updated_before: Option<DateTime<Utc>>,
+
+    #[serde(default, rename = "tag")]
+    pub tags: Vec<String>,
+
+    #[serde(default)]
+    pub tag_match: TagMatch,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct NoteListResponse {
     pub notes: Vec<NoteData>,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
 }
 
 impl NoteListResponse {
     pub fn from_notes(notes: Vec<Note>) -> Self {
         Self {
             notes: notes.into_iter().map(NoteData::from_note).collect(),
+            next_cursor: None,
+            has_more: false,
+        }
+    }
+
+    pub fn from_page(page: NotePage) -> Self {
+        Self {
+            notes: page.notes.into_iter().map(NoteData::from_note).collect(),
+            next_cursor: page.next_cursor.as_ref().map(encode_cursor),
+            has_more: page.has_more,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchNotesQuery {
+    #[validate(length(
+        min = 1,
+        max = 200,
+        message = "Search query must be between 1 and 200 characters"
+    ))]
+    #[param(min_length = 1, max_length = 200)]
+    pub q: String,
+
+    pub cursor: Option<String>,
+
+    #[validate(range(min = 1, max = 100, message = "Limit must be between 1 and 100"))]
+    #[param(minimum = 1, maximum = 100)]
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NoteSearchResult {
+    pub note: NoteData,
+    pub rank: f32,
+    // HTML: the note's text escaped, with matches wrapped in <mark>
+    pub title_highlight: String,
+    pub snippet: String,
+}
+
+impl NoteSearchResult {
+    pub fn from_hit(hit: NoteSearchHit) -> Self {
+        Self {
+            note: NoteData::from_note(hit.note),
+            rank: hit.rank,
+            title_highlight: hit.title_highlight,
+            snippet: hit.snippet,
         }
     }
 }
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NoteSearchResponse {
+    pub results: Vec<NoteSearchResult>,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+impl NoteSearchResponse {
+    pub fn from_page(page: NoteSearchPage) -> Self {
+        Self {
+            results: page
+                .hits
+                .into_iter()
+                .map(NoteSearchResult::from_hit)
+                .collect(),
+            next_cursor: page.next_cursor.as_ref().map(encode_cursor),
+            has_more: page.has_more,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RevisionData {
+    pub revision: i32,
+    pub note_id: Uuid,
+    pub title: String,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RevisionData {
+    pub fn from_revision(revision: NoteRevision) -> Self {
+        Self {
+            revision: revision.revision_number,
+            note_id: revision.note_id,
+            title: revision.title,
+            content: revision.content,
+            created_at: revision.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RevisionResponse {
+    pub revision: RevisionData,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RevisionListResponse {
+    pub revisions: Vec<RevisionData>,
+}
+
+impl RevisionListResponse {
+    pub fn from_revisions(revisions: Vec<NoteRevision>) -> Self {
+        Self {
+            revisions: revisions
+                .into_iter()
+                .map(RevisionData::from_revision)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RevisionDiffQuery {
+    pub from: i32,
+    pub to: i32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RevisionDiffResponse {
+    pub from: RevisionData,
+    pub to: RevisionData,
+    pub title_changed: bool,
+    pub lines: Vec<DiffLine>,
+}
+
+impl RevisionDiffResponse {
+    pub fn from_diff(diff: RevisionDiff) -> Self {
+        Self {
+            title_changed: diff.from.title != diff.to.title,
+            from: RevisionData::from_revision(diff.from),
+            to: RevisionData::from_revision(diff.to),
+            lines: diff.lines,
+        }
+    }
+}
+
+// Cursors are handed to clients as opaque base64 strings
+pub fn encode_cursor<T: Serialize>(cursor: &T) -> String {
+    let json = serde_json::to_vec(cursor).unwrap_or_default();
+    URL_SAFE_NO_PAD.encode(json)
+}
+
+pub fn decode_cursor<T: DeserializeOwned>(cursor: &str) -> Option<T> {
+    let json = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmptyTrashResponse {
+    pub deleted_count: u64,
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use services::models::{TagCount, tag::normalize_tag};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RenameTagRequest {
+    pub tag: RenameTagData,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct RenameTagData {
+    #[validate(custom(function = "validate_tag"))]
+    #[schema(min_length = 1, max_length = 50)]
+    pub name: String,
+}
+
+const MAX_TAG_LENGTH: usize = 50;
+
+// Judged on the stored form, so whitespace-only names are rejected rather than saved empty
+pub fn validate_tag(tag: &str) -> Result<(), ValidationError> {
+    let length = normalize_tag(tag).chars().count();
+    if length == 0 || length > MAX_TAG_LENGTH {
+        return Err(ValidationError::new("length")
+            .with_message("Tags must be between 1 and 50 characters".into()));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TagResponse {
+    pub tag: TagData,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TagData {
+    pub name: String,
+    pub note_count: i64,
+}
+
+impl TagData {
+    pub fn from_tag_count(tag: TagCount) -> Self {
+        Self {
+            name: tag.name,
+            note_count: tag.note_count,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TagListResponse {
+    pub tags: Vec<TagData>,
+}
+
+impl TagListResponse {
+    pub fn from_tag_counts(tags: Vec<TagCount>) -> Self {
+        Self {
+            tags: tags.into_iter().map(TagData::from_tag_count).collect(),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+use tokio::signal;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+// Cancels `shutdown` on the first SIGINT or SIGTERM. Readiness and background
+// tasks watch the token directly; the listeners wait for `stop_accepting`.
+pub async fn watch_signals(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        if let Err(err) = signal::ctrl_c().await {
+            error!("Cannot listen for SIGINT: {}", err);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(err) => {
+                error!("Cannot listen for SIGTERM: {}", err);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+        _ = shutdown.cancelled() => return,
+    }
+
+    info!("Shutdown requested; readiness now reports unavailable");
+    shutdown.cancel();
+}
+
+// Resolves once the server should stop accepting connections: `delay` after shutdown starts
+pub async fn stop_accepting(shutdown: CancellationToken, delay: Duration) {
+    shutdown.cancelled().await;
+    tokio::time::sleep(delay).await;
+}
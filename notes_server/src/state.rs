@@ -1,47 +1,232 @@
 use axum::extract::FromRef;
+use ipnet::IpNet;
 use services::{
     AuthService, AuthServiceTrait, UserRepository, UserService, UserServiceTrait,
-    repositories::{NoteRepository, UserRepositoryTrait, traits::NoteRepositoryTrait},
-    services::{note_service::NoteService, traits::NoteServiceTrait},
+    background::BackgroundTasks,
+    mailer::{FileMailer, Mailer},
+    models::RevisionRetention,
+    password_hasher::{Argon2Hasher, Argon2Params, PasswordHashError, PasswordHasher},
+    rate_limiter::{MemoryRateLimitStore, RateLimit, RateLimitStore},
+    repositories::{
+        LoginAttemptRepository, MfaRepository, NoteRepository, OneTimeTokenRepository,
+        PersonalAccessTokenRepository, RefreshTokenRepository, SessionRepository, TagRepository,
+        UserRepositoryTrait,
+        traits::{
+            LoginAttemptRepositoryTrait, MfaRepositoryTrait, NoteRepositoryTrait,
+            OneTimeTokenRepositoryTrait, PersonalAccessTokenRepositoryTrait,
+            RefreshTokenRepositoryTrait, SessionRepositoryTrait, TagRepositoryTrait,
+        },
+    },
+    services::{
+        AuthConfig, AuthRepositories,
+        jwt_keys::{JwtKeyError, JwtKeys},
+        note_service::NoteService,
+        tag_service::TagService,
+        traits::{NoteServiceTrait, TagServiceTrait},
+    },
 };
-use sqlx::PgPool;
-use std::sync::Arc;
+use sqlx::{
+    PgPool,
+    migrate::{MigrateError, Migrator},
+    postgres::PgPoolOptions,
+};
+use std::{fmt, sync::Arc, time::Duration};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::{Config, NoteSettings},
+    rate_limit::RateLimits,
+};
+
+// Emails waiting to be sent beyond this are dropped rather than queued
+const MAX_BACKGROUND_TASKS: usize = 1_000;
+
+// Also used by the readiness check to spot pending migrations
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
 
 #[derive(Clone, FromRef)]
 pub struct AppState {
     pub db: PgPool,
+    pub health_check_timeout: Duration,
     pub user_service: Arc<dyn UserServiceTrait>,
     pub auth_service: Arc<dyn AuthServiceTrait>,
     pub note_service: Arc<dyn NoteServiceTrait>,
+    pub tag_service: Arc<dyn TagServiceTrait>,
+    pub require_verified_email: bool,
+    pub note_settings: Arc<NoteSettings>,
+    pub rate_limit_store: Arc<dyn RateLimitStore>,
+    pub rate_limits: RateLimits,
+    pub trusted_proxies: Arc<[IpNet]>,
+    // Cancelled when the process starts shutting down
+    pub shutdown: CancellationToken,
+    // Waited for on shutdown, after the server stops
+    pub background_tasks: BackgroundTasks,
+}
+
+#[derive(Debug)]
+pub enum StartupError {
+    Database(sqlx::Error),
+    Migrations(MigrateError),
+    JwtKeys(JwtKeyError),
+    PasswordHasher(PasswordHashError),
 }
 
+impl fmt::Display for StartupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartupError::Database(err) => write!(f, "cannot connect to the database: {}", err),
+            StartupError::Migrations(err) => write!(f, "cannot run migrations: {}", err),
+            StartupError::JwtKeys(err) => write!(f, "cannot load JWT keys: {}", err),
+            StartupError::PasswordHasher(err) => write!(f, "invalid Argon2 parameters: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for StartupError {}
+
 impl AppState {
-    pub async fn new(database_url: &str) -> Result<Self, sqlx::Error> {
+    // `config` has already been validated by `Config::load`
+    pub async fn new(config: &Config) -> Result<Self, StartupError> {
         // Create the database connection pool
-        let db = PgPool::connect(database_url).await?;
+        let db = PgPoolOptions::new()
+            .max_connections(config.database.max_connections)
+            .min_connections(config.database.min_connections)
+            .acquire_timeout(Duration::from_secs(config.database.acquire_timeout_secs))
+            .idle_timeout(Duration::from_secs(config.database.idle_timeout_secs))
+            .connect(config.database.url.expose())
+            .await
+            .map_err(StartupError::Database)?;
 
-        // Run migrations automatically
-        sqlx::migrate!("./migrations").run(&db).await?;
+        // Deployments that migrate as a separate step can turn this off
+        if config.database.run_migrations {
+            MIGRATOR.run(&db).await.map_err(StartupError::Migrations)?;
+        }
 
         let user_repository: Arc<dyn UserRepositoryTrait> =
             Arc::new(UserRepository::new(db.clone()));
 
         let user_service: Arc<dyn UserServiceTrait> = Arc::new(UserService::new(user_repository));
 
-        let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-        let auth_service: Arc<dyn AuthServiceTrait> =
-            Arc::new(AuthService::new(user_service.clone(), jwt_secret));
+        let refresh_token_repository: Arc<dyn RefreshTokenRepositoryTrait> =
+            Arc::new(RefreshTokenRepository::new(db.clone()));
+
+        let session_repository: Arc<dyn SessionRepositoryTrait> =
+            Arc::new(SessionRepository::new(db.clone()));
+
+        let personal_access_token_repository: Arc<dyn PersonalAccessTokenRepositoryTrait> =
+            Arc::new(PersonalAccessTokenRepository::new(db.clone()));
+
+        let one_time_token_repository: Arc<dyn OneTimeTokenRepositoryTrait> =
+            Arc::new(OneTimeTokenRepository::new(db.clone()));
+
+        let mfa_repository: Arc<dyn MfaRepositoryTrait> = Arc::new(MfaRepository::new(db.clone()));
+
+        let login_attempt_repository: Arc<dyn LoginAttemptRepositoryTrait> =
+            Arc::new(LoginAttemptRepository::new(db.clone()));
+
+        // Emails are written to a local spool directory until a real mailer is configured
+        let mailer: Arc<dyn Mailer> = Arc::new(FileMailer::new(
+            config.mail.spool_dir.clone(),
+            config.mail.from.clone(),
+        ));
+
+        let auth = &config.auth;
+
+        // Existing bcrypt hashes keep working and are upgraded as users log in
+        let password_hasher: Arc<dyn PasswordHasher> = Arc::new(
+            Argon2Hasher::new(Argon2Params {
+                memory_kib: auth.argon2_memory_kib,
+                iterations: auth.argon2_iterations,
+                parallelism: auth.argon2_parallelism,
+            })
+            .map_err(StartupError::PasswordHasher)?,
+        );
+
+        // Every key in the directory verifies tokens; only the named one signs new ones
+        let jwt_keys = JwtKeys::load_dir(&auth.jwt_keys_dir, &auth.jwt_signing_key_id)
+            .map_err(StartupError::JwtKeys)?;
+
+        let app_url = config.server.app_url.clone();
+
+        let auth_config = AuthConfig {
+            jwt_keys,
+            jwt_issuer: auth.jwt_issuer.clone().unwrap_or_else(|| app_url.clone()),
+            jwt_audience: auth.jwt_audience.clone(),
+            access_token_ttl: chrono::Duration::seconds(auth.access_token_ttl_secs),
+            refresh_token_ttl: chrono::Duration::days(auth.refresh_token_ttl_days),
+            session_cache_ttl: Duration::from_secs(auth.session_cache_ttl_secs),
+            password_reset_ttl: chrono::Duration::minutes(auth.password_reset_ttl_mins),
+            email_verification_ttl: chrono::Duration::hours(auth.email_verification_ttl_hours),
+            mfa_challenge_ttl: chrono::Duration::seconds(auth.mfa_challenge_ttl_secs),
+            totp_encryption_key: auth
+                .totp_encryption_key()
+                .expect("TOTP key is checked by Config::load"),
+            login_max_failed_attempts: auth.login_max_failed_attempts,
+            login_lockout_duration: chrono::Duration::minutes(auth.login_lockout_mins),
+            login_ip_max_failed_attempts: auth.login_ip_max_failed_attempts,
+            app_url,
+        };
+
+        let auth_repositories = AuthRepositories {
+            refresh_tokens: refresh_token_repository,
+            sessions: session_repository,
+            personal_access_tokens: personal_access_token_repository,
+            one_time_tokens: one_time_token_repository,
+            mfa: mfa_repository,
+            login_attempts: login_attempt_repository,
+        };
+
+        let background_tasks = BackgroundTasks::new(MAX_BACKGROUND_TASKS);
+
+        let auth_service: Arc<dyn AuthServiceTrait> = Arc::new(AuthService::new(
+            user_service.clone(),
+            auth_repositories,
+            mailer,
+            password_hasher,
+            background_tasks.clone(),
+            auth_config,
+        ));
 
         let note_repository: Arc<dyn NoteRepositoryTrait> =
             Arc::new(NoteRepository::new(db.clone()));
 
-        let note_service: Arc<dyn NoteServiceTrait> = Arc::new(NoteService::new(note_repository));
+        let revision_retention = RevisionRetention {
+            max_count: config.notes.revision_max_count,
+            max_age: config
+                .notes
+                .revision_max_age_days
+                .map(chrono::Duration::days),
+        };
+
+        let note_service: Arc<dyn NoteServiceTrait> =
+            Arc::new(NoteService::new(note_repository, revision_retention));
+
+        let tag_repository: Arc<dyn TagRepositoryTrait> = Arc::new(TagRepository::new(db.clone()));
+
+        let tag_service: Arc<dyn TagServiceTrait> = Arc::new(TagService::new(tag_repository));
+
+        let rate_limits = RateLimits {
+            auth: RateLimit::per_minute(config.rate_limit.auth_per_minute),
+            write: RateLimit::per_minute(config.rate_limit.write_per_minute),
+            read: RateLimit::per_minute(config.rate_limit.read_per_minute),
+        };
+
+        let rate_limit_store: Arc<dyn RateLimitStore> = Arc::new(MemoryRateLimitStore::new());
 
         Ok(Self {
             db,
+            health_check_timeout: Duration::from_secs(config.database.health_check_timeout_secs),
             user_service,
             auth_service,
             note_service,
+            tag_service,
+            require_verified_email: auth.require_email_verification,
+            note_settings: Arc::new(config.notes.clone()),
+            rate_limit_store,
+            rate_limits,
+            trusted_proxies: config.server.trusted_proxies.clone().into(),
+            shutdown: CancellationToken::new(),
+            background_tasks,
         })
     }
 }
@@ -0,0 +1,2 @@
+pub mod login_attempt_purge;
+pub mod trash_purge;
@@ -0,0 +1,31 @@
+use services::services::traits::AuthServiceTrait;
+use std::{sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(600);
+
+// Failed logins are only counted within the lockout window, but unknown emails leave rows
+// behind that no successful login ever clears; this deletes them once they expire.
+pub fn spawn_login_attempt_purge(
+    auth_service: Arc<dyn AuthServiceTrait>,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(PURGE_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = ticker.tick() => {}
+            }
+
+            match auth_service.purge_login_attempts().await {
+                Ok(0) => {}
+                Ok(count) => info!(count, "purged expired login attempts"),
+                Err(err) => error!(error = %err, "failed to purge login attempts"),
+            }
+        }
+    })
+}
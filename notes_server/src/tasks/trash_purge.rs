@@ -0,0 +1,32 @@
+use chrono::Duration;
+use services::services::traits::NoteServiceTrait;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+// Periodically hard-deletes notes that have been in the trash longer than `retention`.
+// A purge that is already running finishes before the task exits on `shutdown`.
+pub fn spawn_trash_purge(
+    note_service: Arc<dyn NoteServiceTrait>,
+    retention: Duration,
+    interval: std::time::Duration,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = ticker.tick() => {}
+            }
+
+            match note_service.purge_trash(retention).await {
+                Ok(0) => {}
+                Ok(count) => info!(count, "purged notes from trash"),
+                Err(err) => error!(error = %err, "failed to purge trash"),
+            }
+        }
+    })
+}
@@ -0,0 +1,47 @@
+use std::{future::Future, sync::Arc};
+use tokio::sync::Semaphore;
+use tokio_util::task::TaskTracker;
+use tracing::warn;
+
+// Work that finishes after the response has gone out, such as delivering mail. Tasks are
+// tracked so shutdown can wait for them, and capped so a burst of requests can't queue
+// unbounded work.
+#[derive(Clone)]
+pub struct BackgroundTasks {
+    tracker: TaskTracker,
+    permits: Arc<Semaphore>,
+}
+
+impl BackgroundTasks {
+    pub fn new(max_pending: usize) -> Self {
+        Self {
+            tracker: TaskTracker::new(),
+            permits: Arc::new(Semaphore::new(max_pending)),
+        }
+    }
+
+    // Drops the task with a warning when `max_pending` tasks are already running
+    pub fn spawn<F>(&self, name: &'static str, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let Ok(permit) = Arc::clone(&self.permits).try_acquire_owned() else {
+            warn!(
+                task = name,
+                "too many background tasks pending; dropping task"
+            );
+            return;
+        };
+
+        self.tracker.spawn(async move {
+            task.await;
+            drop(permit);
+        });
+    }
+
+    // Resolves once every task spawned so far has finished
+    pub async fn wait(&self) {
+        self.tracker.close();
+        self.tracker.wait().await;
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{mailer::MailerError, password_hasher::PasswordHashError};
+use chrono::Duration;
+use std::fmt;
+use tracing::warn;
+
+// Postgres SQLSTATE codes that are reported as domain errors instead of storage failures
+const UNIQUE_VIOLATION: &str = "23505";
+const CHECK_VIOLATION: &str = "23514";
+const NOT_NULL_VIOLATION: &str = "23502";
+const STRING_DATA_RIGHT_TRUNCATION: &str = "22001";
+
+enum DatabaseFailure {
+    UniqueViolation(Option<String>),
+    InvalidData(InvalidData),
+    Other,
+}
+
+// The Postgres message names columns, relations and column types, so it only goes to the logs
+enum InvalidData {
+    TooLong,
+    MissingValue,
+    CheckFailed,
+}
+
+fn classify(err: &sqlx::Error) -> DatabaseFailure {
+    let Some(db_err) = err.as_database_error() else {
+        return DatabaseFailure::Other;
+    };
+
+    let invalid = match db_err.code().as_deref() {
+        Some(UNIQUE_VIOLATION) => {
+            return DatabaseFailure::UniqueViolation(db_err.constraint().map(str::to_string));
+        }
+        Some(STRING_DATA_RIGHT_TRUNCATION) => InvalidData::TooLong,
+        Some(NOT_NULL_VIOLATION) => InvalidData::MissingValue,
+        Some(CHECK_VIOLATION) => InvalidData::CheckFailed,
+        _ => return DatabaseFailure::Other,
+    };
+
+    warn!(
+        error = %db_err,
+        constraint = db_err.constraint(),
+        "database rejected invalid data"
+    );
+    DatabaseFailure::InvalidData(invalid)
+}
+
+#[derive(Debug)]
+pub enum UserError {
+    NotFound,
+    Forbidden,
+    Conflict(String),
+    Validation(String),
+    Storage(sqlx::Error),
+}
+
+impl From<sqlx::Error> for UserError {
+    fn from(err: sqlx::Error) -> Self {
+        match classify(&err) {
+            DatabaseFailure::UniqueViolation(constraint) => {
+                let message = match constraint.as_deref() {
+                    Some("users_username_key") => "Username is already taken",
+                    Some("users_email_key") => "Email is already registered",
+                    _ => "User already exists",
+                };
+                UserError::Conflict(message.to_string())
+            }
+            DatabaseFailure::InvalidData(invalid) => {
+                let message = match invalid {
+                    InvalidData::TooLong => "A field is longer than allowed",
+                    InvalidData::MissingValue => "A required field is missing",
+                    InvalidData::CheckFailed => "User data is invalid",
+                };
+                UserError::Validation(message.to_string())
+            }
+            DatabaseFailure::Other => UserError::Storage(err),
+        }
+    }
+}
+
+impl fmt::Display for UserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserError::NotFound => write!(f, "user not found"),
+            UserError::Forbidden => write!(f, "operation not permitted for this user"),
+            UserError::Conflict(message) => write!(f, "conflict: {}", message),
+            UserError::Validation(message) => write!(f, "invalid user data: {}", message),
+            UserError::Storage(err) => write!(f, "storage error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for UserError {}
+
+#[derive(Debug)]
+pub enum NoteError {
+    NotFound,
+    Forbidden,
+    Conflict(String),
+    VersionMismatch,
+    Validation(String),
+    Storage(sqlx::Error),
+}
+
+impl From<sqlx::Error> for NoteError {
+    fn from(err: sqlx::Error) -> Self {
+        match classify(&err) {
+            DatabaseFailure::UniqueViolation(_) => {
+                NoteError::Conflict("Note conflicts with an existing record".to_string())
+            }
+            DatabaseFailure::InvalidData(invalid) => {
+                let message = match invalid {
+                    InvalidData::TooLong => "A field is longer than allowed",
+                    InvalidData::MissingValue => "A required field is missing",
+                    InvalidData::CheckFailed => "Note data is invalid",
+                };
+                NoteError::Validation(message.to_string())
+            }
+            DatabaseFailure::Other => NoteError::Storage(err),
+        }
+    }
+}
+
+impl fmt::Display for NoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoteError::NotFound => write!(f, "note not found"),
+            NoteError::Forbidden => write!(f, "operation not permitted on this note"),
+            NoteError::Conflict(message) => write!(f, "conflict: {}", message),
+            NoteError::VersionMismatch => write!(f, "note version does not match"),
+            NoteError::Validation(message) => write!(f, "invalid note data: {}", message),
+            NoteError::Storage(err) => write!(f, "storage error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for NoteError {}
+
+#[derive(Debug)]
+pub enum AuthError {
+    UserNotFound,
+    InvalidPassword,
+    UserAlreadyExists,
+    User(UserError),
+    DatabaseError(sqlx::Error),
+    PasswordHashError(PasswordHashError),
+    TokenGenerationError,
+    TokenValidationError,
+    RefreshTokenReused,
+    MailDeliveryError(MailerError),
+    InvalidMfaCode,
+    MfaAlreadyEnabled,
+    MfaNotEnabled,
+    SecretEncryptionError,
+    // Too many recent failures; the caller has to wait before trying again
+    TooManyAttempts { retry_after: Duration },
+    AccountLocked { retry_after: Duration },
+}
+
+impl From<sqlx::Error> for AuthError {
+    fn from(err: sqlx::Error) -> Self {
+        AuthError::DatabaseError(err)
+    }
+}
+
+impl From<PasswordHashError> for AuthError {
+    fn from(err: PasswordHashError) -> Self {
+        AuthError::PasswordHashError(err)
+    }
+}
+
+impl From<UserError> for AuthError {
+    fn from(err: UserError) -> Self {
+        match err {
+            UserError::NotFound => AuthError::UserNotFound,
+            UserError::Conflict(_) => AuthError::UserAlreadyExists,
+            UserError::Storage(err) => AuthError::DatabaseError(err),
+            err => AuthError::User(err),
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::UserNotFound => write!(f, "user not found"),
+            AuthError::InvalidPassword => write!(f, "invalid password"),
+            AuthError::UserAlreadyExists => write!(f, "user already exists"),
+            AuthError::User(err) => write!(f, "{}", err),
+            AuthError::DatabaseError(err) => write!(f, "database error: {}", err),
+            AuthError::PasswordHashError(err) => write!(f, "{}", err),
+            AuthError::TokenGenerationError => write!(f, "failed to generate token"),
+            AuthError::TokenValidationError => write!(f, "invalid token"),
+            AuthError::RefreshTokenReused => write!(f, "refresh token reused"),
+            AuthError::MailDeliveryError(err) => write!(f, "{}", err),
+            AuthError::InvalidMfaCode => write!(f, "invalid two-factor code"),
+            AuthError::MfaAlreadyEnabled => write!(f, "two-factor authentication already enabled"),
+            AuthError::MfaNotEnabled => write!(f, "two-factor authentication not enabled"),
+            AuthError::SecretEncryptionError => write!(f, "failed to encrypt or decrypt secret"),
+            AuthError::TooManyAttempts { .. } => write!(f, "too many failed login attempts"),
+            AuthError::AccountLocked { .. } => write!(f, "account temporarily locked"),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
@@ -1,4 +1,9 @@
+pub mod background;
+pub mod errors;
+pub mod mailer;
 pub mod models;
+pub mod password_hasher;
+pub mod rate_limiter;
 pub mod repositories;
 pub mod services;
 
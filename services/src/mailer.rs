@@ -0,0 +1,36 @@
+pub mod file_mailer;
+
+pub use file_mailer::FileMailer;
+
+use async_trait::async_trait;
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub struct MailerError(pub String);
+
+impl fmt::Display for MailerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to send email: {}", self.0)
+    }
+}
+
+impl std::error::Error for MailerError {}
+
+impl From<std::io::Error> for MailerError {
+    fn from(err: std::io::Error) -> Self {
+        MailerError(err.to_string())
+    }
+}
+
+// Outgoing mail goes through this so deployments can plug in SMTP or an API
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), MailerError>;
+}
@@ -0,0 +1,42 @@
+use super::{Email, Mailer, MailerError};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+// Writes each email to a file in a spool directory, for local development
+pub struct FileMailer {
+    spool_dir: PathBuf,
+    from: String,
+}
+
+impl FileMailer {
+    pub fn new(spool_dir: impl Into<PathBuf>, from: impl Into<String>) -> Self {
+        Self {
+            spool_dir: spool_dir.into(),
+            from: from.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> Result<(), MailerError> {
+        tokio::fs::create_dir_all(&self.spool_dir).await?;
+
+        let now = Utc::now();
+        let message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\n\r\n{}\r\n",
+            self.from,
+            email.to,
+            email.subject,
+            now.to_rfc2822(),
+            email.body
+        );
+
+        let file_name = format!("{}-{}.eml", now.format("%Y%m%dT%H%M%S"), Uuid::new_v4());
+        tokio::fs::write(self.spool_dir.join(file_name), message).await?;
+
+        Ok(())
+    }
+}
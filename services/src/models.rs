@@ -1,5 +1,22 @@
+pub mod login_attempt;
+pub mod mfa;
 pub mod note;
+pub mod personal_access_token;
+pub mod revision;
+pub mod session;
+pub mod tag;
+pub mod token;
 pub mod user;
 
-pub use note::Note;
+pub use login_attempt::LoginAttempt;
+pub use mfa::{LoginOutcome, TotpCredential, TotpEnrollment};
+pub use note::{
+    Note, NoteCursor, NoteCursorKey, NoteListFilter, NotePage, NoteSearchCursor, NoteSearchFilter,
+    NoteSearchHit, NoteSearchPage, NoteSortField, SortDirection,
+};
+pub use personal_access_token::{PersonalAccessToken, Scope};
+pub use revision::{DiffLine, DiffOp, NoteRevision, RevisionDiff, RevisionRetention};
+pub use session::{ClientInfo, Credential, Principal, Session};
+pub use tag::{TagCount, TagMatch};
+pub use token::{AuthTokens, OneTimeToken, RefreshToken, TokenPurpose};
 pub use user::User;
@@ -0,0 +1,12 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+// Recent failed logins for one account or IP address
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct LoginAttempt {
+    pub key: String,
+    pub failed_count: i32,
+    pub last_failed_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
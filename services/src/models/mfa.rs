@@ -0,0 +1,36 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use super::{AuthTokens, User};
+
+// The shared secret is stored encrypted; it only becomes active once confirmed
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TotpCredential {
+    pub user_id: Uuid,
+    pub secret_ciphertext: String,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub last_used_step: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TotpCredential {
+    pub fn is_enabled(&self) -> bool {
+        self.confirmed_at.is_some()
+    }
+}
+
+// Returned once when enrolling, for the user to add to an authenticator app
+#[derive(Debug, Clone)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum LoginOutcome {
+    Authenticated(User, AuthTokens),
+    // The password was correct but a second factor is still needed
+    MfaRequired { mfa_token: String },
+}
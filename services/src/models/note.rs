@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
 use uuid::Uuid;
 
+use super::TagMatch;
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Note {
     pub id: Uuid,
@@ -11,4 +13,143 @@ pub struct Note {
     pub content: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub version: i32,
+    #[sqlx(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum NoteSortField {
+    CreatedAt,
+    #[default]
+    UpdatedAt,
+    Title,
+}
+
+impl NoteSortField {
+    pub fn column(&self) -> &'static str {
+        match self {
+            NoteSortField::CreatedAt => "created_at",
+            NoteSortField::UpdatedAt => "updated_at",
+            NoteSortField::Title => "title",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortDirection {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum NoteCursorKey {
+    Timestamp(DateTime<Utc>),
+    Text(String),
+}
+
+// Keyset position of the last note of a page: its sort key plus the id as a tie-breaker
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoteCursor {
+    pub sort: NoteSortField,
+    pub direction: SortDirection,
+    pub key: NoteCursorKey,
+    pub id: Uuid,
+}
+
+impl NoteCursor {
+    pub fn from_note(note: &Note, sort: NoteSortField, direction: SortDirection) -> Self {
+        let key = match sort {
+            NoteSortField::CreatedAt => NoteCursorKey::Timestamp(note.created_at),
+            NoteSortField::UpdatedAt => NoteCursorKey::Timestamp(note.updated_at),
+            NoteSortField::Title => NoteCursorKey::Text(note.title.clone()),
+        };
+
+        Self {
+            sort,
+            direction,
+            key,
+            id: note.id,
+        }
+    }
+
+    // A cursor can only resume the listing it was issued for
+    pub fn matches(&self, sort: NoteSortField, direction: SortDirection) -> bool {
+        let key_matches = matches!(
+            (sort, &self.key),
+            (NoteSortField::Title, NoteCursorKey::Text(_))
+                | (
+                    NoteSortField::CreatedAt | NoteSortField::UpdatedAt,
+                    NoteCursorKey::Timestamp(_)
+                )
+        );
+
+        key_matches && self.sort == sort && self.direction == direction
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NoteListFilter {
+    pub user_id: Uuid,
+    pub sort: NoteSortField,
+    pub direction: SortDirection,
+    pub limit: i64,
+    pub cursor: Option<NoteCursor>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub updated_before: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    pub tag_match: TagMatch,
+}
+
+#[derive(Debug, Clone)]
+pub struct NotePage {
+    pub notes: Vec<Note>,
+    pub next_cursor: Option<NoteCursor>,
+    pub has_more: bool,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct NoteSearchHit {
+    #[sqlx(flatten)]
+    pub note: Note,
+    pub rank: f32,
+    pub title_highlight: String,
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoteSearchCursor {
+    pub rank: f32,
+    pub id: Uuid,
+}
+
+#[derive(Debug, Clone)]
+pub struct NoteSearchFilter {
+    pub user_id: Uuid,
+    pub query: String,
+    pub limit: i64,
+    pub cursor: Option<NoteSearchCursor>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NoteSearchPage {
+    pub hits: Vec<NoteSearchHit>,
+    pub next_cursor: Option<NoteSearchCursor>,
+    pub has_more: bool,
 }
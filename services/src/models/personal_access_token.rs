@@ -0,0 +1,70 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::str::FromStr;
+use uuid::Uuid;
+
+// Personal access tokens start with this so they can be told apart from JWTs
+pub const PERSONAL_ACCESS_TOKEN_PREFIX: &str = "nat_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum Scope {
+    #[serde(rename = "notes:read")]
+    NotesRead,
+    #[serde(rename = "notes:write")]
+    NotesWrite,
+    #[serde(rename = "profile:read")]
+    ProfileRead,
+    #[serde(rename = "profile:write")]
+    ProfileWrite,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::NotesRead => "notes:read",
+            Scope::NotesWrite => "notes:write",
+            Scope::ProfileRead => "profile:read",
+            Scope::ProfileWrite => "profile:write",
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "notes:read" => Ok(Scope::NotesRead),
+            "notes:write" => Ok(Scope::NotesWrite),
+            "profile:read" => Ok(Scope::ProfileRead),
+            "profile:write" => Ok(Scope::ProfileWrite),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PersonalAccessToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub token_prefix: String,
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PersonalAccessToken {
+    // Scopes that are no longer known are dropped rather than failing the token
+    pub fn scopes(&self) -> Vec<Scope> {
+        self.scopes
+            .iter()
+            .filter_map(|scope| scope.parse().ok())
+            .collect()
+    }
+}
@@ -0,0 +1,44 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct NoteRevision {
+    pub id: Uuid,
+    pub note_id: Uuid,
+    pub revision_number: i32,
+    pub title: String,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum DiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DiffLine {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevisionDiff {
+    pub from: NoteRevision,
+    pub to: NoteRevision,
+    pub lines: Vec<DiffLine>,
+}
+
+// How much history is kept per note; the latest revision is always kept
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RevisionRetention {
+    pub max_count: Option<i64>,
+    pub max_age: Option<Duration>,
+}
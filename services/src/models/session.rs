@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use super::personal_access_token::Scope;
+
+// A login on one device; its id doubles as the refresh token family
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+// How a request proved who it is
+#[derive(Debug, Clone)]
+pub enum Credential {
+    Session(Uuid),
+    PersonalAccessToken { token_id: Uuid, scopes: Vec<Scope> },
+}
+
+impl Credential {
+    // Sessions act with the user's full rights; tokens only within their scopes
+    pub fn allows(&self, scope: Scope) -> bool {
+        match self {
+            Credential::Session(_) => true,
+            Credential::PersonalAccessToken { scopes, .. } => scopes.contains(&scope),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub user_id: Uuid,
+    pub credential: Credential,
+}
@@ -0,0 +1,30 @@
+pub mod argon2_hasher;
+
+pub use argon2_hasher::{Argon2Hasher, Argon2Params};
+
+use async_trait::async_trait;
+use std::fmt;
+
+#[derive(Debug)]
+pub struct PasswordHashError(pub String);
+
+impl fmt::Display for PasswordHashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to hash password: {}", self.0)
+    }
+}
+
+impl std::error::Error for PasswordHashError {}
+
+// Passwords are hashed through this so the algorithm and its cost can change
+// without invalidating the hashes already stored
+#[async_trait]
+pub trait PasswordHasher: Send + Sync {
+    async fn hash(&self, password: &str) -> Result<String, PasswordHashError>;
+
+    // Understands every format this hasher has ever produced, not just the current one
+    async fn verify(&self, password: &str, password_hash: &str) -> Result<bool, PasswordHashError>;
+
+    // True when the hash uses an older format or different parameters
+    fn needs_rehash(&self, password_hash: &str) -> bool;
+}
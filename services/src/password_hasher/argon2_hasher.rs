@@ -0,0 +1,125 @@
+use super::{PasswordHashError, PasswordHasher};
+use argon2::{
+    Algorithm, Argon2, Params, PasswordHash, PasswordHasher as _, PasswordVerifier as _, Version,
+    password_hash::SaltString,
+};
+use async_trait::async_trait;
+use rand::rngs::OsRng;
+
+// bcrypt hashes from before the switch to Argon2id start with one of these
+const BCRYPT_PREFIXES: [&str; 3] = ["$2a$", "$2b$", "$2y$"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argon2Params {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+// The OWASP recommendation for Argon2id
+impl Default for Argon2Params {
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+// Hashes with Argon2id and still verifies legacy bcrypt hashes. The work runs
+// on the blocking thread pool so it doesn't hold up other requests.
+pub struct Argon2Hasher {
+    params: Params,
+}
+
+impl Argon2Hasher {
+    pub fn new(params: Argon2Params) -> Result<Self, PasswordHashError> {
+        let params = Params::new(
+            params.memory_kib,
+            params.iterations,
+            params.parallelism,
+            None,
+        )
+        .map_err(|err| PasswordHashError(err.to_string()))?;
+
+        Ok(Self { params })
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+}
+
+#[async_trait]
+impl PasswordHasher for Argon2Hasher {
+    async fn hash(&self, password: &str) -> Result<String, PasswordHashError> {
+        let argon2 = self.argon2();
+        let password = password.to_string();
+
+        run_blocking(move || {
+            let salt = SaltString::generate(&mut OsRng);
+            argon2
+                .hash_password(password.as_bytes(), &salt)
+                .map(|hash| hash.to_string())
+                .map_err(|err| PasswordHashError(err.to_string()))
+        })
+        .await
+    }
+
+    async fn verify(&self, password: &str, password_hash: &str) -> Result<bool, PasswordHashError> {
+        let argon2 = self.argon2();
+        let password = password.to_string();
+        let password_hash = password_hash.to_string();
+
+        run_blocking(move || {
+            if is_bcrypt(&password_hash) {
+                return bcrypt::verify(&password, &password_hash)
+                    .map_err(|err| PasswordHashError(err.to_string()));
+            }
+
+            // Parameters are read from the hash itself, so older ones still verify
+            let parsed = PasswordHash::new(&password_hash)
+                .map_err(|err| PasswordHashError(err.to_string()))?;
+            match argon2.verify_password(password.as_bytes(), &parsed) {
+                Ok(()) => Ok(true),
+                Err(argon2::password_hash::Error::Password) => Ok(false),
+                Err(err) => Err(PasswordHashError(err.to_string())),
+            }
+        })
+        .await
+    }
+
+    fn needs_rehash(&self, password_hash: &str) -> bool {
+        let Ok(parsed) = PasswordHash::new(password_hash) else {
+            return true;
+        };
+        if parsed.algorithm != Algorithm::Argon2id.ident()
+            || parsed.version != Some(Version::V0x13.into())
+        {
+            return true;
+        }
+
+        !Params::try_from(&parsed).is_ok_and(|params| {
+            params.m_cost() == self.params.m_cost()
+                && params.t_cost() == self.params.t_cost()
+                && params.p_cost() == self.params.p_cost()
+        })
+    }
+}
+
+fn is_bcrypt(password_hash: &str) -> bool {
+    BCRYPT_PREFIXES
+        .iter()
+        .any(|prefix| password_hash.starts_with(prefix))
+}
+
+async fn run_blocking<T, F>(task: F) -> Result<T, PasswordHashError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, PasswordHashError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(task)
+        .await
+        .map_err(|err| PasswordHashError(err.to_string()))?
+}
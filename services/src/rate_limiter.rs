@@ -0,0 +1,59 @@
+pub mod memory_store;
+
+pub use memory_store::MemoryRateLimitStore;
+
+use async_trait::async_trait;
+use std::{fmt, time::Duration};
+
+// Token bucket: holds up to `capacity` requests and refills at `capacity` per `period`
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+impl RateLimit {
+    pub fn per_minute(capacity: u32) -> Self {
+        Self {
+            capacity,
+            period: Duration::from_secs(60),
+        }
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        f64::from(self.capacity) / self.period.as_secs_f64()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    // Until the bucket is full again
+    pub reset_after: Duration,
+    // Until the next request would be allowed, when this one wasn't
+    pub retry_after: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct RateLimitError(pub String);
+
+impl fmt::Display for RateLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rate limit store failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for RateLimitError {}
+
+// Buckets live behind this so several instances can share them, e.g. in Redis
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    // Takes one token from the bucket for `key`, creating a full bucket if there is none
+    async fn acquire(
+        &self,
+        key: &str,
+        limit: RateLimit,
+    ) -> Result<RateLimitDecision, RateLimitError>;
+}
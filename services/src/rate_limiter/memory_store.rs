@@ -0,0 +1,256 @@
+use super::{RateLimit, RateLimitDecision, RateLimitError, RateLimitStore};
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// Buckets that refilled completely are forgotten once the map grows past this,
+// at most once per interval so the scan isn't paid on every request
+const PRUNE_THRESHOLD: usize = 10_000;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    // Buckets of different groups share the map, so each remembers its own limit
+    limit: RateLimit,
+}
+
+impl Bucket {
+    fn refilled(&self, now: Instant) -> f64 {
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        (self.tokens + elapsed * self.limit.refill_per_sec()).min(f64::from(self.limit.capacity))
+    }
+}
+
+struct Buckets {
+    by_key: HashMap<String, Bucket>,
+    pruned_at: Instant,
+}
+
+impl Buckets {
+    fn prune(&mut self, now: Instant) {
+        if self.by_key.len() < PRUNE_THRESHOLD
+            || now.duration_since(self.pruned_at) < PRUNE_INTERVAL
+        {
+            return;
+        }
+
+        self.by_key
+            .retain(|_, bucket| bucket.refilled(now) < f64::from(bucket.limit.capacity));
+        self.pruned_at = now;
+    }
+}
+
+// Per-process buckets; each server instance enforces its own limits
+pub struct MemoryRateLimitStore {
+    buckets: Mutex<Buckets>,
+}
+
+impl MemoryRateLimitStore {
+    pub fn new() -> Self {
+        Self {
+            buckets: Mutex::new(Buckets {
+                by_key: HashMap::new(),
+                pruned_at: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl Default for MemoryRateLimitStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn acquire(
+        &self,
+        key: &str,
+        limit: RateLimit,
+    ) -> Result<RateLimitDecision, RateLimitError> {
+        self.acquire_at(key, limit, Instant::now())
+    }
+}
+
+impl MemoryRateLimitStore {
+    fn acquire_at(
+        &self,
+        key: &str,
+        limit: RateLimit,
+        now: Instant,
+    ) -> Result<RateLimitDecision, RateLimitError> {
+        let capacity = f64::from(limit.capacity);
+        let refill_per_sec = limit.refill_per_sec();
+
+        let mut buckets = self
+            .buckets
+            .lock()
+            .map_err(|err| RateLimitError(err.to_string()))?;
+
+        buckets.prune(now);
+
+        let bucket = buckets.by_key.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+            limit,
+        });
+
+        bucket.limit = limit;
+        bucket.tokens = bucket.refilled(now);
+        bucket.updated_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        let retry_after =
+            (!allowed).then(|| Duration::from_secs_f64((1.0 - bucket.tokens) / refill_per_sec));
+
+        Ok(RateLimitDecision {
+            allowed,
+            limit: limit.capacity,
+            remaining: bucket.tokens.floor() as u32,
+            reset_after: Duration::from_secs_f64((capacity - bucket.tokens) / refill_per_sec),
+            retry_after,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One token every second
+    fn limit() -> RateLimit {
+        RateLimit::per_minute(60)
+    }
+
+    fn drain(store: &MemoryRateLimitStore, key: &str, limit: RateLimit, now: Instant) {
+        for _ in 0..limit.capacity {
+            assert!(store.acquire_at(key, limit, now).unwrap().allowed);
+        }
+    }
+
+    #[test]
+    fn refills_up_to_capacity() {
+        let store = MemoryRateLimitStore::new();
+        let start = Instant::now();
+        drain(&store, "read:ip:1", limit(), start);
+
+        let decision = store
+            .acquire_at("read:ip:1", limit(), start + Duration::from_secs(10))
+            .unwrap();
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 9);
+
+        // Idle far longer than the period, the bucket holds no more than its capacity
+        let decision = store
+            .acquire_at("read:ip:1", limit(), start + Duration::from_secs(3_600))
+            .unwrap();
+        assert!(decision.allowed);
+        assert_eq!(decision.limit, 60);
+        assert_eq!(decision.remaining, 59);
+    }
+
+    #[test]
+    fn denied_request_reports_when_to_retry() {
+        let store = MemoryRateLimitStore::new();
+        let start = Instant::now();
+        drain(&store, "read:ip:1", limit(), start);
+
+        let decision = store.acquire_at("read:ip:1", limit(), start).unwrap();
+        assert!(!decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        assert_eq!(decision.retry_after, Some(Duration::from_secs(1)));
+        assert_eq!(decision.reset_after, Duration::from_secs(60));
+
+        // Half a token later, only the other half is left to wait for
+        let decision = store
+            .acquire_at("read:ip:1", limit(), start + Duration::from_millis(500))
+            .unwrap();
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after, Some(Duration::from_millis(500)));
+
+        let decision = store
+            .acquire_at("read:ip:1", limit(), start + Duration::from_secs(1))
+            .unwrap();
+        assert!(decision.allowed);
+    }
+
+    #[test]
+    fn keys_have_separate_buckets() {
+        let store = MemoryRateLimitStore::new();
+        let now = Instant::now();
+        drain(&store, "read:ip:1", limit(), now);
+
+        assert!(store.acquire_at("read:ip:2", limit(), now).unwrap().allowed);
+        assert!(
+            store
+                .acquire_at("write:ip:1", limit(), now)
+                .unwrap()
+                .allowed
+        );
+    }
+
+    #[test]
+    fn prune_drops_only_full_buckets_of_each_group() {
+        let start = Instant::now();
+        let auth = RateLimit::per_minute(5);
+        let read = RateLimit::per_minute(600);
+        let bucket = |tokens: f64, updated_at: Instant, limit: RateLimit| Bucket {
+            tokens,
+            updated_at,
+            limit,
+        };
+
+        let mut by_key: HashMap<String, Bucket> = (0..PRUNE_THRESHOLD)
+            .map(|i| (format!("read:ip:{i}"), bucket(600.0, start, read)))
+            .collect();
+        let now = start + PRUNE_INTERVAL;
+        let recently = now - Duration::from_secs(1);
+
+        // One second refills 10 read tokens but only a twelfth of an auth token
+        by_key.insert("auth:empty".into(), bucket(0.0, recently, auth));
+        by_key.insert("auth:refilled".into(), bucket(0.0, start, auth));
+        by_key.insert("read:nearly".into(), bucket(595.0, recently, read));
+        by_key.insert("read:draining".into(), bucket(500.0, recently, read));
+
+        let mut buckets = Buckets {
+            by_key,
+            pruned_at: start,
+        };
+        buckets.prune(now);
+
+        let mut kept: Vec<&str> = buckets.by_key.keys().map(String::as_str).collect();
+        kept.sort();
+        assert_eq!(kept, ["auth:empty", "read:draining"]);
+        assert_eq!(buckets.pruned_at, now);
+    }
+
+    #[test]
+    fn prune_waits_for_the_interval() {
+        let start = Instant::now();
+        let mut buckets = Buckets {
+            by_key: (0..PRUNE_THRESHOLD)
+                .map(|i| {
+                    let bucket = Bucket {
+                        tokens: 60.0,
+                        updated_at: start,
+                        limit: limit(),
+                    };
+                    (format!("read:ip:{i}"), bucket)
+                })
+                .collect(),
+            pruned_at: start,
+        };
+
+        buckets.prune(start + PRUNE_INTERVAL - Duration::from_secs(1));
+        assert_eq!(buckets.by_key.len(), PRUNE_THRESHOLD);
+    }
+}
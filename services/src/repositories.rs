@@ -1,7 +1,21 @@
+pub mod login_attempt_repository;
+pub mod mfa_repository;
 pub mod note_repository;
+pub mod one_time_token_repository;
+pub mod personal_access_token_repository;
+pub mod refresh_token_repository;
+pub mod session_repository;
+pub mod tag_repository;
 pub mod traits;
 pub mod user_repository;
 
+pub use login_attempt_repository::LoginAttemptRepository;
+pub use mfa_repository::MfaRepository;
 pub use note_repository::NoteRepository;
+pub use one_time_token_repository::OneTimeTokenRepository;
+pub use personal_access_token_repository::PersonalAccessTokenRepository;
+pub use refresh_token_repository::RefreshTokenRepository;
+pub use session_repository::SessionRepository;
+pub use tag_repository::TagRepository;
 pub use traits::UserRepositoryTrait;
 pub use user_repository::UserRepository;
@@ -0,0 +1,116 @@
+use super::traits::LoginAttemptRepositoryTrait;
+use crate::{errors::AuthError, models::LoginAttempt};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::instrument;
+
+#[derive(Clone)]
+pub struct LoginAttemptRepository {
+    db: PgPool,
+}
+
+impl LoginAttemptRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepositoryTrait for LoginAttemptRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn find(&self, keys: &[String]) -> Result<Vec<LoginAttempt>, AuthError> {
+        let attempts = sqlx::query_as::<_, LoginAttempt>(
+            r#"
+            SELECT key, failed_count, last_failed_at, locked_until
+            FROM login_attempts
+            WHERE key = ANY($1)
+            "#,
+        )
+        .bind(keys)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(attempts)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn record_failure(
+        &self,
+        key: &str,
+        window_start: DateTime<Utc>,
+    ) -> Result<LoginAttempt, AuthError> {
+        // Failures from before the window no longer count
+        let attempt = sqlx::query_as::<_, LoginAttempt>(
+            r#"
+            INSERT INTO login_attempts (key, failed_count, last_failed_at)
+            VALUES ($1, 1, NOW())
+            ON CONFLICT (key) DO UPDATE
+            SET failed_count = CASE
+                    WHEN login_attempts.last_failed_at < $2 THEN 1
+                    ELSE login_attempts.failed_count + 1
+                END,
+                locked_until = CASE
+                    WHEN login_attempts.last_failed_at < $2 THEN NULL
+                    ELSE login_attempts.locked_until
+                END,
+                last_failed_at = NOW()
+            RETURNING key, failed_count, last_failed_at, locked_until
+            "#,
+        )
+        .bind(key)
+        .bind(window_start)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(attempt)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn lock(&self, key: &str, locked_until: DateTime<Utc>) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            UPDATE login_attempts
+            SET locked_until = $2
+            WHERE key = $1
+            "#,
+        )
+        .bind(key)
+        .bind(locked_until)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn clear(&self, key: &str) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            DELETE FROM login_attempts
+            WHERE key = $1
+            "#,
+        )
+        .bind(key)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_stale(&self, before: DateTime<Utc>) -> Result<u64, AuthError> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM login_attempts
+            WHERE last_failed_at < $1
+            AND (locked_until IS NULL OR locked_until < NOW())
+            "#,
+        )
+        .bind(before)
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
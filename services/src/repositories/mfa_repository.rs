@@ -0,0 +1,208 @@
+use super::traits::MfaRepositoryTrait;
+use crate::{errors::AuthError, models::TotpCredential};
+use async_trait::async_trait;
+use sqlx::{PgConnection, PgPool};
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct MfaRepository {
+    db: PgPool,
+}
+
+impl MfaRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    async fn insert_recovery_codes(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        code_hashes: &[String],
+    ) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO recovery_codes (user_id, code_hash)
+            SELECT $1, UNNEST($2::varchar[])
+            "#,
+        )
+        .bind(user_id)
+        .bind(code_hashes)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MfaRepositoryTrait for MfaRepository {
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn find_totp(&self, user_id: Uuid) -> Result<Option<TotpCredential>, AuthError> {
+        let credential = sqlx::query_as::<_, TotpCredential>(
+            r#"
+            SELECT user_id, secret_ciphertext, confirmed_at, last_used_step, created_at
+            FROM totp_credentials
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(credential)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn save_pending_totp(
+        &self,
+        user_id: Uuid,
+        secret_ciphertext: &str,
+    ) -> Result<Option<TotpCredential>, AuthError> {
+        // Starting over replaces an unconfirmed secret but never an active one
+        let credential = sqlx::query_as::<_, TotpCredential>(
+            r#"
+            INSERT INTO totp_credentials (user_id, secret_ciphertext)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret_ciphertext = EXCLUDED.secret_ciphertext,
+                last_used_step = NULL,
+                created_at = NOW()
+            WHERE totp_credentials.confirmed_at IS NULL
+            RETURNING user_id, secret_ciphertext, confirmed_at, last_used_step, created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(secret_ciphertext)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(credential)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn confirm_totp(
+        &self,
+        user_id: Uuid,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> Result<bool, AuthError> {
+        let mut tx = self.db.begin().await?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE totp_credentials
+            SET confirmed_at = NOW(),
+                last_used_step = $2
+            WHERE user_id = $1
+            AND confirmed_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(step)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        Self::insert_recovery_codes(&mut tx, user_id, recovery_code_hashes).await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn use_totp_step(&self, user_id: Uuid, step: i64) -> Result<bool, AuthError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE totp_credentials
+            SET last_used_step = $2
+            WHERE user_id = $1
+            AND confirmed_at IS NOT NULL
+            AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+        )
+        .bind(user_id)
+        .bind(step)
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str) -> Result<bool, AuthError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE recovery_codes
+            SET used_at = NOW()
+            WHERE user_id = $1
+            AND code_hash = $2
+            AND used_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(code_hash)
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        recovery_code_hashes: &[String],
+    ) -> Result<(), AuthError> {
+        let mut tx = self.db.begin().await?;
+
+        Self::insert_recovery_codes(&mut tx, user_id, recovery_code_hashes).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn delete(&self, user_id: Uuid) -> Result<bool, AuthError> {
+        let mut tx = self.db.begin().await?;
+
+        let result = sqlx::query(
+            r#"
+            DELETE FROM totp_credentials
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
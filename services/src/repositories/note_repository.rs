@@ -1,9 +1,26 @@
 use super::traits::NoteRepositoryTrait;
-use crate::models::Note;
+use crate::{
+    errors::NoteError,
+    models::{
+        Note, NoteCursor, NoteCursorKey, NoteListFilter, NotePage, NoteRevision, NoteSearchCursor,
+        NoteSearchFilter, NoteSearchHit, NoteSearchPage, SortDirection, TagMatch,
+    },
+};
 use async_trait::async_trait;
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgConnection, PgPool, Postgres, QueryBuilder};
+use std::collections::HashMap;
+use tracing::instrument;
 use uuid::Uuid;
 
+// The updated note and whether its title or content differ from before
+#[derive(FromRow)]
+struct UpdatedNote {
+    #[sqlx(flatten)]
+    note: Note,
+    text_changed: bool,
+}
+
 #[derive(Clone)]
 pub struct NoteRepository {
     db: PgPool,
@@ -13,38 +30,160 @@ impl NoteRepository {
     pub fn new(db: PgPool) -> Self {
         Self { db }
     }
+
+    async fn find_tags(&self, note_ids: &[Uuid]) -> Result<HashMap<Uuid, Vec<String>>, NoteError> {
+        let rows = sqlx::query_as::<_, (Uuid, String)>(
+            r#"
+            SELECT nt.note_id, t.name
+            FROM note_tags nt
+            JOIN tags t ON t.id = nt.tag_id
+            WHERE nt.note_id = ANY($1)
+            ORDER BY t.name
+            "#,
+        )
+        .bind(note_ids)
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut tags: HashMap<Uuid, Vec<String>> = HashMap::new();
+        for (note_id, name) in rows {
+            tags.entry(note_id).or_default().push(name);
+        }
+
+        Ok(tags)
+    }
+
+    async fn attach_tags<'a>(
+        &self,
+        notes: impl IntoIterator<Item = &'a mut Note>,
+    ) -> Result<(), NoteError> {
+        let notes: Vec<&mut Note> = notes.into_iter().collect();
+        let note_ids: Vec<Uuid> = notes.iter().map(|note| note.id).collect();
+        if note_ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut tags = self.find_tags(&note_ids).await?;
+        for note in notes {
+            note.tags = tags.remove(&note.id).unwrap_or_default();
+        }
+
+        Ok(())
+    }
+
+    // Snapshots the note's current title and content as its next revision
+    async fn insert_revision(conn: &mut PgConnection, note: &Note) -> Result<(), NoteError> {
+        sqlx::query(
+            r#"
+            INSERT INTO note_revisions (note_id, revision_number, title, content)
+            SELECT $1, COALESCE(MAX(revision_number), 0) + 1, $2, $3
+            FROM note_revisions
+            WHERE note_id = $1
+            "#,
+        )
+        .bind(note.id)
+        .bind(&note.title)
+        .bind(&note.content)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn replace_tags(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        note_id: Uuid,
+        tags: &[String],
+    ) -> Result<(), NoteError> {
+        sqlx::query("DELETE FROM note_tags WHERE note_id = $1")
+            .bind(note_id)
+            .execute(&mut *conn)
+            .await?;
+
+        if tags.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO tags (user_id, name)
+            SELECT $1, name FROM UNNEST($2::text[]) AS name
+            ON CONFLICT (user_id, name) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(tags)
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO note_tags (note_id, tag_id)
+            SELECT $1, id
+            FROM tags
+            WHERE user_id = $2
+            AND name = ANY($3)
+            "#,
+        )
+        .bind(note_id)
+        .bind(user_id)
+        .bind(tags)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl NoteRepositoryTrait for NoteRepository {
-    async fn create(&self, user_id: Uuid, title: &str, content: &str) -> Result<Note, sqlx::Error> {
-        let note = sqlx::query_as::<_, Note>(
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn create(
+        &self,
+        user_id: Uuid,
+        title: &str,
+        content: &str,
+        tags: &[String],
+    ) -> Result<Note, NoteError> {
+        let mut tx = self.db.begin().await?;
+
+        let mut note = sqlx::query_as::<_, Note>(
             r#"
             INSERT INTO notes (user_id, title, content)
             VALUES ($1, $2, $3)
-            RETURNING id, user_id, title, content, created_at, updated_at
+            RETURNING id, user_id, title, content, created_at, updated_at, deleted_at,
+                      version
             "#,
         )
         .bind(user_id)
         .bind(title)
         .bind(content)
-        .fetch_one(&self.db)
+        .fetch_one(&mut *tx)
         .await?;
 
+        Self::insert_revision(&mut tx, &note).await?;
+        Self::replace_tags(&mut tx, user_id, note.id, tags).await?;
+        tx.commit().await?;
+
+        note.tags = tags.to_vec();
         Ok(note)
     }
 
+    #[instrument(level = "debug", skip_all, fields(note_id = %note_id, user_id = %user_id))]
     async fn find_note_by_id(
         &self,
         note_id: Uuid,
         user_id: Uuid,
-    ) -> Result<Option<Note>, sqlx::Error> {
-        let user = sqlx::query_as::<_, Note>(
+    ) -> Result<Option<Note>, NoteError> {
+        let mut note = sqlx::query_as::<_, Note>(
             r#"
-            SELECT id, user_id, title, content, created_at, updated_at
+            SELECT id, user_id, title, content, created_at, updated_at, deleted_at,
+                   version
             FROM notes
             WHERE id = $1
             AND user_id = $2
+            AND deleted_at IS NULL
             "#,
         )
         .bind(note_id)
@@ -52,64 +191,408 @@ impl NoteRepositoryTrait for NoteRepository {
         .fetch_optional(&self.db)
         .await?;
 
-        Ok(user)
+        self.attach_tags(note.as_mut()).await?;
+
+        Ok(note)
     }
 
-    async fn find_all_notes(&self, user_id: Uuid) -> Result<Vec<Note>, sqlx::Error> {
-        let notes = sqlx::query_as::<_, Note>(
+    #[instrument(level = "debug", skip_all)]
+    async fn find_all_notes(&self, filter: &NoteListFilter) -> Result<NotePage, NoteError> {
+        let column = filter.sort.column();
+        let direction = filter.direction.keyword();
+
+        let mut query = QueryBuilder::<Postgres>::new(
             r#"
-            SELECT id, user_id, title, content, created_at, updated_at
+            SELECT id, user_id, title, content, created_at, updated_at, deleted_at,
+                   version
             FROM notes
-            WHERE user_id = $1
+            WHERE user_id = "#,
+        );
+        query.push_bind(filter.user_id);
+        query.push(" AND deleted_at IS NULL");
+
+        if let Some(created_after) = filter.created_after {
+            query.push(" AND created_at > ").push_bind(created_after);
+        }
+
+        if let Some(updated_before) = filter.updated_before {
+            query.push(" AND updated_at < ").push_bind(updated_before);
+        }
+
+        if !filter.tags.is_empty() {
+            match filter.tag_match {
+                TagMatch::Any => {
+                    query.push(
+                        " AND EXISTS (SELECT 1 FROM note_tags nt JOIN tags t ON t.id = nt.tag_id \
+                         WHERE nt.note_id = notes.id AND t.name = ANY(",
+                    );
+                    query.push_bind(filter.tags.clone()).push("))");
+                }
+                TagMatch::All => {
+                    query.push(
+                        " AND (SELECT COUNT(DISTINCT t.name) FROM note_tags nt \
+                         JOIN tags t ON t.id = nt.tag_id \
+                         WHERE nt.note_id = notes.id AND t.name = ANY(",
+                    );
+                    query.push_bind(filter.tags.clone()).push(")) = ");
+                    query.push_bind(filter.tags.len() as i64);
+                }
+            }
+        }
+
+        if let Some(cursor) = &filter.cursor {
+            let comparison = match filter.direction {
+                SortDirection::Asc => ">",
+                SortDirection::Desc => "<",
+            };
+
+            query.push(format!(" AND ({column}, id) {comparison} ("));
+            match &cursor.key {
+                NoteCursorKey::Timestamp(value) => query.push_bind(*value),
+                NoteCursorKey::Text(value) => query.push_bind(value.clone()),
+            };
+            query.push(", ").push_bind(cursor.id).push(")");
+        }
+
+        // Fetch one extra row to find out whether another page follows
+        query.push(format!(
+            " ORDER BY {column} {direction}, id {direction} LIMIT "
+        ));
+        query.push_bind(filter.limit + 1);
+
+        let mut notes = query.build_query_as::<Note>().fetch_all(&self.db).await?;
+
+        let has_more = notes.len() as i64 > filter.limit;
+        notes.truncate(filter.limit as usize);
+        self.attach_tags(notes.iter_mut()).await?;
+
+        let next_cursor = if has_more {
+            notes
+                .last()
+                .map(|note| NoteCursor::from_note(note, filter.sort, filter.direction))
+        } else {
+            None
+        };
+
+        Ok(NotePage {
+            notes,
+            next_cursor,
+            has_more,
+        })
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn search(&self, filter: &NoteSearchFilter) -> Result<NoteSearchPage, NoteError> {
+        let (cursor_rank, cursor_id) = match &filter.cursor {
+            Some(cursor) => (Some(cursor.rank), Some(cursor.id)),
+            None => (None, None),
+        };
+
+        // Rank and paginate first so headlines are only built for the returned page.
+        // The text is escaped first so <mark> is the only markup in a headline.
+        let mut hits = sqlx::query_as::<_, NoteSearchHit>(
+            r#"
+            WITH search AS (
+                SELECT websearch_to_tsquery('english', $2) AS query
+            ),
+            hits AS (
+                SELECT n.id, n.user_id, n.title, n.content, n.created_at, n.updated_at,
+                       n.deleted_at, n.version, ts_rank(n.search_vector, search.query) AS rank
+                FROM notes n, search
+                WHERE n.user_id = $1
+                AND n.deleted_at IS NULL
+                AND n.search_vector @@ search.query
+                AND ($3::real IS NULL
+                     OR (ts_rank(n.search_vector, search.query), n.id) < ($3::real, $4::uuid))
+                ORDER BY rank DESC, n.id DESC
+                LIMIT $5
+            )
+            SELECT hits.id, hits.user_id, hits.title, hits.content, hits.created_at,
+                   hits.updated_at, hits.deleted_at, hits.version, hits.rank,
+                   ts_headline('english', escape_html(COALESCE(hits.title, '')), search.query,
+                               'HighlightAll=true, StartSel=<mark>, StopSel=</mark>')
+                       AS title_highlight,
+                   ts_headline('english', escape_html(hits.content), search.query,
+                               'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=30, MinWords=10')
+                       AS snippet
+            FROM hits, search
+            ORDER BY hits.rank DESC, hits.id DESC
             "#,
         )
-        .bind(user_id)
+        .bind(filter.user_id)
+        .bind(&filter.query)
+        .bind(cursor_rank)
+        .bind(cursor_id)
+        .bind(filter.limit + 1)
         .fetch_all(&self.db)
         .await?;
 
-        Ok(notes)
+        let has_more = hits.len() as i64 > filter.limit;
+        hits.truncate(filter.limit as usize);
+        self.attach_tags(hits.iter_mut().map(|hit| &mut hit.note))
+            .await?;
+
+        let next_cursor = if has_more {
+            hits.last().map(|hit| NoteSearchCursor {
+                rank: hit.rank,
+                id: hit.note.id,
+            })
+        } else {
+            None
+        };
+
+        Ok(NoteSearchPage {
+            hits,
+            next_cursor,
+            has_more,
+        })
     }
 
+    #[instrument(level = "debug", skip_all, fields(note_id = %note_id, user_id = %user_id))]
     async fn update(
         &self,
         note_id: Uuid,
         user_id: Uuid,
         title: Option<&str>,
         content: Option<&str>,
-    ) -> Result<Option<Note>, sqlx::Error> {
-        let note = sqlx::query_as::<_, Note>(
+        tags: Option<&[String]>,
+        expected_versions: Option<&[i32]>,
+    ) -> Result<Option<Note>, NoteError> {
+        let mut tx = self.db.begin().await?;
+
+        let Some(UpdatedNote {
+            mut note,
+            text_changed,
+        }) = sqlx::query_as::<_, UpdatedNote>(
+            r#"
+            WITH previous AS (
+                SELECT id, title, content
+                FROM notes
+                WHERE id = $1
+                AND user_id = $2
+                AND deleted_at IS NULL
+                AND ($5::int[] IS NULL OR version = ANY($5))
+                FOR UPDATE
+            )
+            UPDATE notes n
+            SET title = COALESCE($3, n.title),
+                content = COALESCE($4, n.content),
+                version = n.version + 1
+            FROM previous
+            WHERE n.id = previous.id
+            RETURNING n.id, n.user_id, n.title, n.content, n.created_at, n.updated_at,
+                      n.deleted_at, n.version,
+                      (n.title IS DISTINCT FROM previous.title
+                       OR n.content IS DISTINCT FROM previous.content) AS text_changed
+            "#,
+        )
+        .bind(note_id)
+        .bind(user_id)
+        .bind(title)
+        .bind(content)
+        .bind(expected_versions)
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        // Revisions hold title and content only, so a tags-only edit would
+        // just repeat the latest one
+        if text_changed {
+            Self::insert_revision(&mut tx, &note).await?;
+        }
+        if let Some(tags) = tags {
+            Self::replace_tags(&mut tx, user_id, note_id, tags).await?;
+        }
+        tx.commit().await?;
+
+        self.attach_tags([&mut note]).await?;
+
+        Ok(Some(note))
+    }
+
+    #[instrument(level = "debug", skip_all, fields(note_id = %note_id, user_id = %user_id))]
+    async fn delete(&self, note_id: Uuid, user_id: Uuid) -> Result<Option<Note>, NoteError> {
+        let mut note = sqlx::query_as::<_, Note>(
             r#"
             UPDATE notes
-            SET title = COALESCE($3, title),
-                content = COALESCE($4, content)
+            SET deleted_at = NOW()
             WHERE id = $1
             AND user_id = $2
-            RETURNING id, user_id, title, content, created_at, updated_at
+            AND deleted_at IS NULL
+            RETURNING id, user_id, title, content, created_at, updated_at, deleted_at,
+                      version
             "#,
         )
         .bind(note_id)
         .bind(user_id)
-        .bind(title)
-        .bind(content)
         .fetch_optional(&self.db)
         .await?;
 
+        self.attach_tags(note.as_mut()).await?;
+
         Ok(note)
     }
 
-    async fn delete(&self, note_id: Uuid) -> Result<Option<Note>, sqlx::Error> {
-        let note = sqlx::query_as::<_, Note>(
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn find_deleted_notes(&self, user_id: Uuid) -> Result<Vec<Note>, NoteError> {
+        let mut notes = sqlx::query_as::<_, Note>(
             r#"
-            DELETE FROM notes
-            WHERE
-            id = $1
-            RETURNING id, user_id, title, content, created_at, updated_at
+            SELECT id, user_id, title, content, created_at, updated_at, deleted_at,
+                   version
+            FROM notes
+            WHERE user_id = $1
+            AND deleted_at IS NOT NULL
+            ORDER BY deleted_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        self.attach_tags(notes.iter_mut()).await?;
+
+        Ok(notes)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(note_id = %note_id, user_id = %user_id))]
+    async fn restore(&self, note_id: Uuid, user_id: Uuid) -> Result<Option<Note>, NoteError> {
+        let mut note = sqlx::query_as::<_, Note>(
+            r#"
+            UPDATE notes
+            SET deleted_at = NULL
+            WHERE id = $1
+            AND user_id = $2
+            AND deleted_at IS NOT NULL
+            RETURNING id, user_id, title, content, created_at, updated_at, deleted_at,
+                      version
             "#,
         )
         .bind(note_id)
+        .bind(user_id)
         .fetch_optional(&self.db)
         .await?;
 
+        self.attach_tags(note.as_mut()).await?;
+
         Ok(note)
     }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn empty_trash(&self, user_id: Uuid) -> Result<u64, NoteError> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM notes
+            WHERE user_id = $1
+            AND deleted_at IS NOT NULL
+            "#,
+        )
+        .bind(user_id)
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn purge_deleted_before(&self, cutoff: DateTime<Utc>) -> Result<u64, NoteError> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM notes
+            WHERE deleted_at IS NOT NULL
+            AND deleted_at < $1
+            "#,
+        )
+        .bind(cutoff)
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    #[instrument(level = "debug", skip_all, fields(note_id = %note_id, user_id = %user_id))]
+    async fn find_revisions(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<NoteRevision>, NoteError> {
+        let revisions = sqlx::query_as::<_, NoteRevision>(
+            r#"
+            SELECT r.id, r.note_id, r.revision_number, r.title, r.content, r.created_at
+            FROM note_revisions r
+            JOIN notes n ON n.id = r.note_id
+            WHERE r.note_id = $1
+            AND n.user_id = $2
+            AND n.deleted_at IS NULL
+            ORDER BY r.revision_number DESC
+            "#,
+        )
+        .bind(note_id)
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(revisions)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(note_id = %note_id, user_id = %user_id))]
+    async fn find_revision(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+        revision_number: i32,
+    ) -> Result<Option<NoteRevision>, NoteError> {
+        let revision = sqlx::query_as::<_, NoteRevision>(
+            r#"
+            SELECT r.id, r.note_id, r.revision_number, r.title, r.content, r.created_at
+            FROM note_revisions r
+            JOIN notes n ON n.id = r.note_id
+            WHERE r.note_id = $1
+            AND n.user_id = $2
+            AND n.deleted_at IS NULL
+            AND r.revision_number = $3
+            "#,
+        )
+        .bind(note_id)
+        .bind(user_id)
+        .bind(revision_number)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(revision)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(note_id = %note_id))]
+    async fn prune_revisions(
+        &self,
+        note_id: Uuid,
+        max_count: Option<i64>,
+        created_before: Option<DateTime<Utc>>,
+    ) -> Result<u64, NoteError> {
+        let result = sqlx::query(
+            r#"
+            WITH latest AS (
+                SELECT MAX(revision_number) AS revision_number
+                FROM note_revisions
+                WHERE note_id = $1
+            )
+            DELETE FROM note_revisions r
+            USING latest
+            WHERE r.note_id = $1
+            AND r.revision_number < latest.revision_number
+            AND (
+                ($2::bigint IS NOT NULL AND r.revision_number <= latest.revision_number - $2)
+                OR ($3::timestamptz IS NOT NULL AND r.created_at < $3)
+            )
+            "#,
+        )
+        .bind(note_id)
+        .bind(max_count)
+        .bind(created_before)
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
 }
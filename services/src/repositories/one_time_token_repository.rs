@@ -0,0 +1,121 @@
+use super::traits::OneTimeTokenRepositoryTrait;
+use crate::{
+    errors::AuthError,
+    models::{OneTimeToken, TokenPurpose},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct OneTimeTokenRepository {
+    db: PgPool,
+}
+
+impl OneTimeTokenRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl OneTimeTokenRepositoryTrait for OneTimeTokenRepository {
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn create(
+        &self,
+        user_id: Uuid,
+        purpose: TokenPurpose,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<OneTimeToken, AuthError> {
+        let token = sqlx::query_as::<_, OneTimeToken>(
+            r#"
+            INSERT INTO one_time_tokens (user_id, purpose, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, user_id, purpose, token_hash, expires_at, used_at, created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(purpose.as_str())
+        .bind(token_hash)
+        .bind(expires_at)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(token)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_active(
+        &self,
+        purpose: TokenPurpose,
+        token_hash: &str,
+    ) -> Result<Option<OneTimeToken>, AuthError> {
+        let token = sqlx::query_as::<_, OneTimeToken>(
+            r#"
+            SELECT id, user_id, purpose, token_hash, expires_at, used_at, created_at
+            FROM one_time_tokens
+            WHERE token_hash = $1
+            AND purpose = $2
+            AND used_at IS NULL
+            AND expires_at > NOW()
+            "#,
+        )
+        .bind(token_hash)
+        .bind(purpose.as_str())
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(token)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn consume(
+        &self,
+        purpose: TokenPurpose,
+        token_hash: &str,
+    ) -> Result<Option<OneTimeToken>, AuthError> {
+        let token = sqlx::query_as::<_, OneTimeToken>(
+            r#"
+            UPDATE one_time_tokens
+            SET used_at = NOW()
+            WHERE token_hash = $1
+            AND purpose = $2
+            AND used_at IS NULL
+            AND expires_at > NOW()
+            RETURNING id, user_id, purpose, token_hash, expires_at, used_at, created_at
+            "#,
+        )
+        .bind(token_hash)
+        .bind(purpose.as_str())
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(token)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn invalidate_for_user(
+        &self,
+        user_id: Uuid,
+        purpose: TokenPurpose,
+    ) -> Result<u64, AuthError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE one_time_tokens
+            SET used_at = NOW()
+            WHERE user_id = $1
+            AND purpose = $2
+            AND used_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(purpose.as_str())
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,145 @@
+use super::traits::PersonalAccessTokenRepositoryTrait;
+use crate::{errors::AuthError, models::PersonalAccessToken};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct PersonalAccessTokenRepository {
+    db: PgPool,
+}
+
+impl PersonalAccessTokenRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl PersonalAccessTokenRepositoryTrait for PersonalAccessTokenRepository {
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn create(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        token_prefix: &str,
+        token_hash: &str,
+        scopes: &[String],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<PersonalAccessToken, AuthError> {
+        let token = sqlx::query_as::<_, PersonalAccessToken>(
+            r#"
+            INSERT INTO personal_access_tokens
+                (user_id, name, token_prefix, token_hash, scopes, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, name, token_prefix, token_hash, scopes, expires_at,
+                      last_used_at, revoked_at, created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(token_prefix)
+        .bind(token_hash)
+        .bind(scopes)
+        .bind(expires_at)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(token)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<PersonalAccessToken>, AuthError> {
+        let token = sqlx::query_as::<_, PersonalAccessToken>(
+            r#"
+            SELECT id, user_id, name, token_prefix, token_hash, scopes, expires_at,
+                   last_used_at, revoked_at, created_at
+            FROM personal_access_tokens
+            WHERE token_hash = $1
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(token)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn find_by_user(&self, user_id: Uuid) -> Result<Vec<PersonalAccessToken>, AuthError> {
+        let tokens = sqlx::query_as::<_, PersonalAccessToken>(
+            r#"
+            SELECT id, user_id, name, token_prefix, token_hash, scopes, expires_at,
+                   last_used_at, revoked_at, created_at
+            FROM personal_access_tokens
+            WHERE user_id = $1
+            AND revoked_at IS NULL
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(tokens)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(token_id = %token_id))]
+    async fn touch(&self, token_id: Uuid, used_before: DateTime<Utc>) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            UPDATE personal_access_tokens
+            SET last_used_at = NOW()
+            WHERE id = $1
+            AND (last_used_at IS NULL OR last_used_at < $2)
+            "#,
+        )
+        .bind(token_id)
+        .bind(used_before)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id, token_id = %token_id))]
+    async fn revoke(&self, user_id: Uuid, token_id: Uuid) -> Result<bool, AuthError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE personal_access_tokens
+            SET revoked_at = NOW()
+            WHERE id = $1
+            AND user_id = $2
+            AND revoked_at IS NULL
+            "#,
+        )
+        .bind(token_id)
+        .bind(user_id)
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn revoke_all(&self, user_id: Uuid) -> Result<u64, AuthError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE personal_access_tokens
+            SET revoked_at = NOW()
+            WHERE user_id = $1
+            AND revoked_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
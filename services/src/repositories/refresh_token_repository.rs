@@ -0,0 +1,111 @@
+use super::traits::RefreshTokenRepositoryTrait;
+use crate::{errors::AuthError, models::RefreshToken};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct RefreshTokenRepository {
+    db: PgPool,
+}
+
+impl RefreshTokenRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepositoryTrait for RefreshTokenRepository {
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id, family_id = %family_id))]
+    async fn create(
+        &self,
+        user_id: Uuid,
+        family_id: Uuid,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<RefreshToken, AuthError> {
+        let token = sqlx::query_as::<_, RefreshToken>(
+            r#"
+            INSERT INTO refresh_tokens (user_id, family_id, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, user_id, family_id, token_hash, expires_at, rotated_at,
+                      revoked_at, created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(family_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(token)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, AuthError> {
+        let token = sqlx::query_as::<_, RefreshToken>(
+            r#"
+            SELECT id, user_id, family_id, token_hash, expires_at, rotated_at,
+                   revoked_at, created_at
+            FROM refresh_tokens
+            WHERE token_hash = $1
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(token)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(token_id = %token_id))]
+    async fn rotate(
+        &self,
+        token_id: Uuid,
+        new_token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<RefreshToken>, AuthError> {
+        let mut tx = self.db.begin().await?;
+
+        // Only one caller can retire a token; anyone else is replaying it
+        let Some((user_id, family_id)) = sqlx::query_as::<_, (Uuid, Uuid)>(
+            r#"
+            UPDATE refresh_tokens
+            SET rotated_at = NOW()
+            WHERE id = $1
+            AND rotated_at IS NULL
+            AND revoked_at IS NULL
+            RETURNING user_id, family_id
+            "#,
+        )
+        .bind(token_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let token = sqlx::query_as::<_, RefreshToken>(
+            r#"
+            INSERT INTO refresh_tokens (user_id, family_id, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, user_id, family_id, token_hash, expires_at, rotated_at,
+                      revoked_at, created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(family_id)
+        .bind(new_token_hash)
+        .bind(expires_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(token))
+    }
+}
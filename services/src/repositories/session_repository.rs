@@ -0,0 +1,185 @@
+use super::traits::SessionRepositoryTrait;
+use crate::{
+    errors::AuthError,
+    models::{ClientInfo, Session},
+};
+use async_trait::async_trait;
+use sqlx::{PgConnection, PgPool};
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct SessionRepository {
+    db: PgPool,
+}
+
+impl SessionRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    async fn revoke_refresh_tokens(
+        conn: &mut PgConnection,
+        session_ids: &[Uuid],
+    ) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = NOW()
+            WHERE family_id = ANY($1)
+            AND revoked_at IS NULL
+            "#,
+        )
+        .bind(session_ids)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionRepositoryTrait for SessionRepository {
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn create(&self, user_id: Uuid, client: &ClientInfo) -> Result<Session, AuthError> {
+        let session = sqlx::query_as::<_, Session>(
+            r#"
+            INSERT INTO sessions (user_id, user_agent, ip_address)
+            VALUES ($1, $2, $3)
+            RETURNING id, user_id, user_agent, ip_address, created_at, last_used_at,
+                      revoked_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(&client.user_agent)
+        .bind(&client.ip_address)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(session)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(session_id = %session_id))]
+    async fn find_by_id(&self, session_id: Uuid) -> Result<Option<Session>, AuthError> {
+        let session = sqlx::query_as::<_, Session>(
+            r#"
+            SELECT id, user_id, user_agent, ip_address, created_at, last_used_at,
+                   revoked_at
+            FROM sessions
+            WHERE id = $1
+            "#,
+        )
+        .bind(session_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(session)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn find_active_by_user(&self, user_id: Uuid) -> Result<Vec<Session>, AuthError> {
+        // A session outlives its access tokens only while it has a usable refresh token
+        let sessions = sqlx::query_as::<_, Session>(
+            r#"
+            SELECT s.id, s.user_id, s.user_agent, s.ip_address, s.created_at, s.last_used_at,
+                   s.revoked_at
+            FROM sessions s
+            WHERE s.user_id = $1
+            AND s.revoked_at IS NULL
+            AND EXISTS (
+                SELECT 1
+                FROM refresh_tokens rt
+                WHERE rt.family_id = s.id
+                AND rt.rotated_at IS NULL
+                AND rt.revoked_at IS NULL
+                AND rt.expires_at > NOW()
+            )
+            ORDER BY s.last_used_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(session_id = %session_id))]
+    async fn touch(&self, session_id: Uuid, client: &ClientInfo) -> Result<(), AuthError> {
+        sqlx::query(
+            r#"
+            UPDATE sessions
+            SET last_used_at = NOW(),
+                user_agent = COALESCE($2, user_agent),
+                ip_address = COALESCE($3, ip_address)
+            WHERE id = $1
+            "#,
+        )
+        .bind(session_id)
+        .bind(&client.user_agent)
+        .bind(&client.ip_address)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id, session_id = %session_id))]
+    async fn revoke(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, AuthError> {
+        let mut tx = self.db.begin().await?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE sessions
+            SET revoked_at = NOW()
+            WHERE id = $1
+            AND user_id = $2
+            AND revoked_at IS NULL
+            "#,
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        Self::revoke_refresh_tokens(&mut tx, &[session_id]).await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn revoke_all(
+        &self,
+        user_id: Uuid,
+        except_session_id: Option<Uuid>,
+    ) -> Result<Vec<Uuid>, AuthError> {
+        let mut tx = self.db.begin().await?;
+
+        let session_ids = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            UPDATE sessions
+            SET revoked_at = NOW()
+            WHERE user_id = $1
+            AND revoked_at IS NULL
+            AND ($2::uuid IS NULL OR id <> $2)
+            RETURNING id
+            "#,
+        )
+        .bind(user_id)
+        .bind(except_session_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        Self::revoke_refresh_tokens(&mut tx, &session_ids).await?;
+
+        tx.commit().await?;
+
+        Ok(session_ids)
+    }
+}
@@ -0,0 +1,151 @@
+use super::traits::TagRepositoryTrait;
+use crate::{errors::NoteError, models::TagCount};
+use async_trait::async_trait;
+use sqlx::PgPool;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct TagRepository {
+    db: PgPool,
+}
+
+impl TagRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    async fn count(&self, user_id: Uuid, name: &str) -> Result<TagCount, NoteError> {
+        let tag = sqlx::query_as::<_, TagCount>(
+            r#"
+            SELECT t.name, COUNT(n.id) AS note_count
+            FROM tags t
+            LEFT JOIN note_tags nt ON nt.tag_id = t.id
+            LEFT JOIN notes n ON n.id = nt.note_id AND n.deleted_at IS NULL
+            WHERE t.user_id = $1
+            AND t.name = $2
+            GROUP BY t.name
+            "#,
+        )
+        .bind(user_id)
+        .bind(name)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(tag)
+    }
+}
+
+#[async_trait]
+impl TagRepositoryTrait for TagRepository {
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn find_all_with_counts(&self, user_id: Uuid) -> Result<Vec<TagCount>, NoteError> {
+        let tags = sqlx::query_as::<_, TagCount>(
+            r#"
+            SELECT t.name, COUNT(n.id) AS note_count
+            FROM tags t
+            JOIN note_tags nt ON nt.tag_id = t.id
+            JOIN notes n ON n.id = nt.note_id AND n.deleted_at IS NULL
+            WHERE t.user_id = $1
+            GROUP BY t.name
+            ORDER BY note_count DESC, t.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(tags)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn rename(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        new_name: &str,
+    ) -> Result<Option<TagCount>, NoteError> {
+        let mut tx = self.db.begin().await?;
+
+        let Some(source_id) = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT id
+            FROM tags
+            WHERE user_id = $1
+            AND name = $2
+            FOR UPDATE
+            "#,
+        )
+        .bind(user_id)
+        .bind(name)
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let target_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT id
+            FROM tags
+            WHERE user_id = $1
+            AND name = $2
+            FOR UPDATE
+            "#,
+        )
+        .bind(user_id)
+        .bind(new_name)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        // Every note carrying the tag changes, so cached ETags and If-Match versions go stale
+        if target_id != Some(source_id) {
+            sqlx::query(
+                r#"
+                UPDATE notes
+                SET version = version + 1, updated_at = NOW()
+                WHERE id IN (SELECT note_id FROM note_tags WHERE tag_id = $1)
+                "#,
+            )
+            .bind(source_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        match target_id {
+            // Renaming onto an existing tag merges the two
+            Some(target_id) if target_id != source_id => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO note_tags (note_id, tag_id)
+                    SELECT note_id, $2
+                    FROM note_tags
+                    WHERE tag_id = $1
+                    ON CONFLICT DO NOTHING
+                    "#,
+                )
+                .bind(source_id)
+                .bind(target_id)
+                .execute(&mut *tx)
+                .await?;
+
+                sqlx::query("DELETE FROM tags WHERE id = $1")
+                    .bind(source_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            Some(_) => {}
+            None => {
+                sqlx::query("UPDATE tags SET name = $2 WHERE id = $1")
+                    .bind(source_id)
+                    .bind(new_name)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        self.count(user_id, new_name).await.map(Some)
+    }
+}
@@ -1,6 +1,13 @@
-use crate::models::{Note, User};
+use crate::{
+    errors::{AuthError, NoteError, UserError},
+    models::{
+        ClientInfo, LoginAttempt, Note, NoteListFilter, NotePage, NoteRevision, NoteSearchFilter,
+        NoteSearchPage, OneTimeToken, PersonalAccessToken, RefreshToken, Session, TagCount,
+        TokenPurpose, TotpCredential, User,
+    },
+};
 use async_trait::async_trait;
-use sqlx::Error as SqlxError;
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
 #[async_trait]
@@ -10,35 +17,51 @@ pub trait UserRepositoryTrait: Send + Sync {
         username: &str,
         email: &str,
         password_hash: &str,
-    ) -> Result<User, SqlxError>;
+    ) -> Result<User, UserError>;
 
-    async fn find_by_id(&self, user_id: Uuid) -> Result<Option<User>, SqlxError>;
+    async fn find_by_id(&self, user_id: Uuid) -> Result<Option<User>, UserError>;
 
-    async fn find_by_email(&self, email: &str) -> Result<Option<User>, SqlxError>;
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, UserError>;
 
-    async fn find_by_username(&self, username: &str) -> Result<Option<User>, SqlxError>;
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, UserError>;
 
     async fn update(
         &self,
         user_id: Uuid,
         username: Option<&str>,
         email: Option<&str>,
-        bio: Option<&str>,
-        image: Option<&str>,
-    ) -> Result<Option<User>, SqlxError>;
+        bio: Option<Option<&str>>,
+        image: Option<Option<&str>>,
+    ) -> Result<Option<User>, UserError>;
+
+    async fn update_password(
+        &self,
+        user_id: Uuid,
+        password_hash: &str,
+    ) -> Result<Option<User>, UserError>;
+
+    async fn mark_email_verified(&self, user_id: Uuid) -> Result<Option<User>, UserError>;
 }
 
 #[async_trait]
 pub trait NoteRepositoryTrait: Send + Sync {
-    async fn create(&self, user_id: Uuid, title: &str, content: &str) -> Result<Note, SqlxError>;
+    async fn create(
+        &self,
+        user_id: Uuid,
+        title: &str,
+        content: &str,
+        tags: &[String],
+    ) -> Result<Note, NoteError>;
 
     async fn find_note_by_id(
         &self,
         note_id: Uuid,
         user_id: Uuid,
-    ) -> Result<Option<Note>, SqlxError>;
+    ) -> Result<Option<Note>, NoteError>;
+
+    async fn find_all_notes(&self, filter: &NoteListFilter) -> Result<NotePage, NoteError>;
 
-    async fn find_all_notes(&self, user_id: Uuid) -> Result<Vec<Note>, SqlxError>;
+    async fn search(&self, filter: &NoteSearchFilter) -> Result<NoteSearchPage, NoteError>;
 
     async fn update(
         &self,
@@ -46,7 +69,202 @@ pub trait NoteRepositoryTrait: Send + Sync {
         user_id: Uuid,
         title: Option<&str>,
         content: Option<&str>,
-    ) -> Result<Option<Note>, SqlxError>;
+        tags: Option<&[String]>,
+        expected_versions: Option<&[i32]>,
+    ) -> Result<Option<Note>, NoteError>;
+
+    async fn delete(&self, note_id: Uuid, user_id: Uuid) -> Result<Option<Note>, NoteError>;
+
+    async fn find_deleted_notes(&self, user_id: Uuid) -> Result<Vec<Note>, NoteError>;
+
+    async fn restore(&self, note_id: Uuid, user_id: Uuid) -> Result<Option<Note>, NoteError>;
+
+    async fn empty_trash(&self, user_id: Uuid) -> Result<u64, NoteError>;
+
+    async fn purge_deleted_before(&self, cutoff: DateTime<Utc>) -> Result<u64, NoteError>;
+
+    async fn find_revisions(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<NoteRevision>, NoteError>;
+
+    async fn find_revision(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+        revision_number: i32,
+    ) -> Result<Option<NoteRevision>, NoteError>;
+
+    async fn prune_revisions(
+        &self,
+        note_id: Uuid,
+        max_count: Option<i64>,
+        created_before: Option<DateTime<Utc>>,
+    ) -> Result<u64, NoteError>;
+}
+
+#[async_trait]
+pub trait TagRepositoryTrait: Send + Sync {
+    async fn find_all_with_counts(&self, user_id: Uuid) -> Result<Vec<TagCount>, NoteError>;
+
+    async fn rename(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        new_name: &str,
+    ) -> Result<Option<TagCount>, NoteError>;
+}
+
+#[async_trait]
+pub trait RefreshTokenRepositoryTrait: Send + Sync {
+    async fn create(
+        &self,
+        user_id: Uuid,
+        family_id: Uuid,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<RefreshToken, AuthError>;
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, AuthError>;
+
+    // Marks the token as used and issues its successor in the same family;
+    // returns None if the token was already rotated or revoked
+    async fn rotate(
+        &self,
+        token_id: Uuid,
+        new_token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<RefreshToken>, AuthError>;
+}
+
+#[async_trait]
+pub trait SessionRepositoryTrait: Send + Sync {
+    async fn create(&self, user_id: Uuid, client: &ClientInfo) -> Result<Session, AuthError>;
+
+    async fn find_by_id(&self, session_id: Uuid) -> Result<Option<Session>, AuthError>;
+
+    async fn find_active_by_user(&self, user_id: Uuid) -> Result<Vec<Session>, AuthError>;
+
+    async fn touch(&self, session_id: Uuid, client: &ClientInfo) -> Result<(), AuthError>;
+
+    // Revoking a session also revokes every refresh token issued for it
+    async fn revoke(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, AuthError>;
+
+    async fn revoke_all(
+        &self,
+        user_id: Uuid,
+        except_session_id: Option<Uuid>,
+    ) -> Result<Vec<Uuid>, AuthError>;
+}
+
+#[async_trait]
+pub trait PersonalAccessTokenRepositoryTrait: Send + Sync {
+    async fn create(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        token_prefix: &str,
+        token_hash: &str,
+        scopes: &[String],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<PersonalAccessToken, AuthError>;
+
+    async fn find_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<PersonalAccessToken>, AuthError>;
+
+    async fn find_by_user(&self, user_id: Uuid) -> Result<Vec<PersonalAccessToken>, AuthError>;
+
+    // Records a use unless one was already recorded after `used_before`
+    async fn touch(&self, token_id: Uuid, used_before: DateTime<Utc>) -> Result<(), AuthError>;
+
+    async fn revoke(&self, user_id: Uuid, token_id: Uuid) -> Result<bool, AuthError>;
+
+    async fn revoke_all(&self, user_id: Uuid) -> Result<u64, AuthError>;
+}
+
+#[async_trait]
+pub trait OneTimeTokenRepositoryTrait: Send + Sync {
+    async fn create(
+        &self,
+        user_id: Uuid,
+        purpose: TokenPurpose,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<OneTimeToken, AuthError>;
+
+    // An unexpired, unused token, left unused
+    async fn find_active(
+        &self,
+        purpose: TokenPurpose,
+        token_hash: &str,
+    ) -> Result<Option<OneTimeToken>, AuthError>;
+
+    // Marks an unexpired, unused token as used; None if there was no such token
+    async fn consume(
+        &self,
+        purpose: TokenPurpose,
+        token_hash: &str,
+    ) -> Result<Option<OneTimeToken>, AuthError>;
+
+    async fn invalidate_for_user(
+        &self,
+        user_id: Uuid,
+        purpose: TokenPurpose,
+    ) -> Result<u64, AuthError>;
+}
+
+#[async_trait]
+pub trait MfaRepositoryTrait: Send + Sync {
+    async fn find_totp(&self, user_id: Uuid) -> Result<Option<TotpCredential>, AuthError>;
+
+    // None if the user already has a confirmed secret
+    async fn save_pending_totp(
+        &self,
+        user_id: Uuid,
+        secret_ciphertext: &str,
+    ) -> Result<Option<TotpCredential>, AuthError>;
+
+    // Activates the pending secret and replaces any recovery codes
+    async fn confirm_totp(
+        &self,
+        user_id: Uuid,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> Result<bool, AuthError>;
+
+    // False if a code from this step or a later one was already accepted
+    async fn use_totp_step(&self, user_id: Uuid, step: i64) -> Result<bool, AuthError>;
+
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str) -> Result<bool, AuthError>;
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        recovery_code_hashes: &[String],
+    ) -> Result<(), AuthError>;
+
+    // Removes the secret and recovery codes
+    async fn delete(&self, user_id: Uuid) -> Result<bool, AuthError>;
+}
+
+#[async_trait]
+pub trait LoginAttemptRepositoryTrait: Send + Sync {
+    async fn find(&self, keys: &[String]) -> Result<Vec<LoginAttempt>, AuthError>;
+
+    // Counts a failure, starting over if the last one was before window_start
+    async fn record_failure(
+        &self,
+        key: &str,
+        window_start: DateTime<Utc>,
+    ) -> Result<LoginAttempt, AuthError>;
+
+    async fn lock(&self, key: &str, locked_until: DateTime<Utc>) -> Result<(), AuthError>;
+
+    async fn clear(&self, key: &str) -> Result<(), AuthError>;
 
-    async fn delete(&self, note_id: Uuid) -> Result<Option<Note>, SqlxError>;
+    // Deletes unlocked rows whose last failure was before `before`
+    async fn delete_stale(&self, before: DateTime<Utc>) -> Result<u64, AuthError>;
 }
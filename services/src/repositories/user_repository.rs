@@ -1,7 +1,8 @@
 use super::traits::UserRepositoryTrait;
-use crate::models::User;
+use crate::{errors::UserError, models::User};
 use async_trait::async_trait;
 use sqlx::PgPool;
+use tracing::instrument;
 use uuid::Uuid;
 
 #[derive(Clone)]
@@ -17,18 +18,19 @@ impl UserRepository {
 
 #[async_trait]
 impl UserRepositoryTrait for UserRepository {
+    #[instrument(level = "debug", skip_all)]
     async fn create(
         &self,
         username: &str,
         email: &str,
         password_hash: &str,
-    ) -> Result<User, sqlx::Error> {
+    ) -> Result<User, UserError> {
         let user = sqlx::query_as::<_, User>(
             r#"
             INSERT INTO users (username, email, password_hash)
             VALUES ($1, $2, $3)
             RETURNING id, username, email, password_hash, bio, image,
-                      created_at, updated_at
+                      email_verified_at, created_at, updated_at
             "#,
         )
         .bind(username)
@@ -40,11 +42,12 @@ impl UserRepositoryTrait for UserRepository {
         Ok(user)
     }
 
-    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, sqlx::Error> {
+    #[instrument(level = "debug", skip_all, fields(id = %id))]
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, UserError> {
         let user = sqlx::query_as::<_, User>(
             r#"
             SELECT id, username, email, password_hash, bio, image,
-                   created_at, updated_at
+                   email_verified_at, created_at, updated_at
             FROM users
             WHERE id = $1
             "#,
@@ -56,11 +59,12 @@ impl UserRepositoryTrait for UserRepository {
         Ok(user)
     }
 
-    async fn find_by_email(&self, email: &str) -> Result<Option<User>, sqlx::Error> {
+    #[instrument(level = "debug", skip_all)]
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, UserError> {
         let user = sqlx::query_as::<_, User>(
             r#"
             SELECT id, username, email, password_hash, bio, image,
-                   created_at, updated_at
+                   email_verified_at, created_at, updated_at
             FROM users
             WHERE email = $1
             "#,
@@ -72,11 +76,12 @@ impl UserRepositoryTrait for UserRepository {
         Ok(user)
     }
 
-    async fn find_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error> {
+    #[instrument(level = "debug", skip_all)]
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, UserError> {
         let user = sqlx::query_as::<_, User>(
             r#"
             SELECT id, username, email, password_hash, bio, image,
-                   created_at, updated_at
+                   email_verified_at, created_at, updated_at
             FROM users
             WHERE username = $1
             "#,
@@ -88,31 +93,81 @@ impl UserRepositoryTrait for UserRepository {
         Ok(user)
     }
 
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
     async fn update(
         &self,
         user_id: Uuid,
         username: Option<&str>,
         email: Option<&str>,
-        bio: Option<&str>,
-        image: Option<&str>,
-    ) -> Result<Option<User>, sqlx::Error> {
+        bio: Option<Option<&str>>,
+        image: Option<Option<&str>>,
+    ) -> Result<Option<User>, UserError> {
+        // bio and image use a separate flag so that an explicit null clears them;
+        // a new email address has to be verified again
         let user = sqlx::query_as::<_, User>(
             r#"
             UPDATE users
             SET username = COALESCE($2, username),
                 email = COALESCE($3, email),
-                bio = COALESCE($4, bio),
-                image = COALESCE($5, image)
+                email_verified_at = CASE
+                    WHEN $3 IS NOT NULL AND $3 <> email THEN NULL
+                    ELSE email_verified_at
+                END,
+                bio = CASE WHEN $4 THEN $5 ELSE bio END,
+                image = CASE WHEN $6 THEN $7 ELSE image END
             WHERE id = $1
             RETURNING id, username, email, password_hash, bio, image,
-                      created_at, updated_at
+                      email_verified_at, created_at, updated_at
             "#,
         )
         .bind(user_id)
         .bind(username)
         .bind(email)
-        .bind(bio)
-        .bind(image)
+        .bind(bio.is_some())
+        .bind(bio.flatten())
+        .bind(image.is_some())
+        .bind(image.flatten())
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(user)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn update_password(
+        &self,
+        user_id: Uuid,
+        password_hash: &str,
+    ) -> Result<Option<User>, UserError> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            UPDATE users
+            SET password_hash = $2
+            WHERE id = $1
+            RETURNING id, username, email, password_hash, bio, image,
+                      email_verified_at, created_at, updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(password_hash)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(user)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(user_id = %user_id))]
+    async fn mark_email_verified(&self, user_id: Uuid) -> Result<Option<User>, UserError> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            UPDATE users
+            SET email_verified_at = COALESCE(email_verified_at, NOW())
+            WHERE id = $1
+            RETURNING id, username, email, password_hash, bio, image,
+                      email_verified_at, created_at, updated_at
+            "#,
+        )
+        .bind(user_id)
         .fetch_optional(&self.db)
         .await?;
 
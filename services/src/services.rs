@@ -1,8 +1,14 @@
 pub mod auth_service;
+pub mod jwt_keys;
 pub mod note_service;
+pub(crate) mod opaque_token;
+pub(crate) mod secret_cipher;
+pub(crate) mod session_cache;
+pub mod tag_service;
+pub(crate) mod totp;
 pub mod traits;
 pub mod user_service;
 
-pub use auth_service::AuthService;
+pub use auth_service::{AuthConfig, AuthRepositories, AuthService};
 pub use traits::{AuthServiceTrait, UserServiceTrait};
 pub use user_service::UserService;
@@ -1,46 +1,151 @@
 use crate::{
-    models::User,
+    background::BackgroundTasks,
+    mailer::{Email, Mailer},
+    models::{
+        AuthTokens, ClientInfo, Credential, LoginOutcome, PersonalAccessToken, Principal, Scope,
+        Session, TokenPurpose, TotpEnrollment, User,
+        personal_access_token::PERSONAL_ACCESS_TOKEN_PREFIX,
+    },
+    password_hasher::PasswordHasher,
+    repositories::traits::{
+        LoginAttemptRepositoryTrait, MfaRepositoryTrait, OneTimeTokenRepositoryTrait,
+        PersonalAccessTokenRepositoryTrait, RefreshTokenRepositoryTrait, SessionRepositoryTrait,
+    },
     services::{
         UserServiceTrait,
+        jwt_keys::{JwkSet, JwtKeys},
+        opaque_token,
+        secret_cipher::SecretCipher,
+        session_cache::SessionCache,
+        totp,
         traits::{AuthError, AuthServiceTrait},
     },
 };
 use async_trait::async_trait;
-use chrono::{Duration, Utc};
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use chrono::{DateTime, Duration, Utc};
+use data_encoding::BASE32_NOPAD;
+use jsonwebtoken::{Validation, decode, decode_header};
+use rand::{RngCore, rngs::OsRng};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::{fmt, sync::Arc};
+use tokio::sync::OnceCell;
+use tracing::{Instrument, debug, error, instrument, warn};
 use uuid::Uuid;
 
+// last_used_at on personal access tokens is only written this often
+const TOKEN_USAGE_RESOLUTION_SECS: i64 = 60;
+
+// Shown as the service name in authenticator apps
+const TOTP_ISSUER: &str = "Notes";
+
+const RECOVERY_CODE_COUNT: usize = 10;
+
+// Failures allowed before each further attempt has to wait, doubling up to the cap
+const ACCOUNT_FREE_ATTEMPTS: i32 = 3;
+const IP_FREE_ATTEMPTS: i32 = 10;
+const MAX_BACKOFF_SECS: i64 = 60;
+
 pub struct AuthService {
     user_service: Arc<dyn UserServiceTrait>,
-    jwt_secret: String,
+    refresh_token_repository: Arc<dyn RefreshTokenRepositoryTrait>,
+    session_repository: Arc<dyn SessionRepositoryTrait>,
+    personal_access_token_repository: Arc<dyn PersonalAccessTokenRepositoryTrait>,
+    one_time_token_repository: Arc<dyn OneTimeTokenRepositoryTrait>,
+    mfa_repository: Arc<dyn MfaRepositoryTrait>,
+    login_attempt_repository: Arc<dyn LoginAttemptRepositoryTrait>,
+    mailer: Arc<dyn Mailer>,
+    password_hasher: Arc<dyn PasswordHasher>,
+    background_tasks: BackgroundTasks,
+    session_cache: SessionCache,
+    secret_cipher: SecretCipher,
+    // Verified against when the email is unknown, so both paths cost the same
+    dummy_password_hash: OnceCell<String>,
+    config: AuthConfig,
+}
+
+pub struct AuthConfig {
+    pub jwt_keys: JwtKeys,
+    // Written into every access token and required when validating one
+    pub jwt_issuer: String,
+    pub jwt_audience: String,
+    pub access_token_ttl: Duration,
+    pub refresh_token_ttl: Duration,
+    pub session_cache_ttl: std::time::Duration,
+    pub password_reset_ttl: Duration,
+    pub email_verification_ttl: Duration,
+    // How long the password step of a two-factor login stays valid
+    pub mfa_challenge_ttl: Duration,
+    // AES-256 key for TOTP secrets at rest
+    pub totp_encryption_key: [u8; 32],
+    // Failed logins before an account is locked, and for how long; failures
+    // older than the lockout duration are forgotten
+    pub login_max_failed_attempts: i32,
+    pub login_lockout_duration: Duration,
+    // Failed logins from one IP address before it is blocked for the same duration
+    pub login_ip_max_failed_attempts: i32,
+    // Base URL of the web app, used for links in emails
+    pub app_url: String,
 }
 
 #[derive(Serialize, Deserialize)]
 struct Claims {
+    iss: String,
+    aud: String,
     sub: String,
+    sid: String,
+    jti: String,
     exp: usize,
     iat: usize,
 }
 
+// The storage AuthService needs besides users, grouped to keep the constructor manageable
+pub struct AuthRepositories {
+    pub refresh_tokens: Arc<dyn RefreshTokenRepositoryTrait>,
+    pub sessions: Arc<dyn SessionRepositoryTrait>,
+    pub personal_access_tokens: Arc<dyn PersonalAccessTokenRepositoryTrait>,
+    pub one_time_tokens: Arc<dyn OneTimeTokenRepositoryTrait>,
+    pub mfa: Arc<dyn MfaRepositoryTrait>,
+    pub login_attempts: Arc<dyn LoginAttemptRepositoryTrait>,
+}
+
 impl AuthService {
-    pub fn new(user_service: Arc<dyn UserServiceTrait>, jwt_secret: String) -> Self {
+    pub fn new(
+        user_service: Arc<dyn UserServiceTrait>,
+        repositories: AuthRepositories,
+        mailer: Arc<dyn Mailer>,
+        password_hasher: Arc<dyn PasswordHasher>,
+        background_tasks: BackgroundTasks,
+        config: AuthConfig,
+    ) -> Self {
         Self {
             user_service,
-            jwt_secret,
+            refresh_token_repository: repositories.refresh_tokens,
+            session_repository: repositories.sessions,
+            personal_access_token_repository: repositories.personal_access_tokens,
+            one_time_token_repository: repositories.one_time_tokens,
+            mfa_repository: repositories.mfa,
+            login_attempt_repository: repositories.login_attempts,
+            mailer,
+            password_hasher,
+            background_tasks,
+            session_cache: SessionCache::new(config.session_cache_ttl),
+            secret_cipher: SecretCipher::new(&config.totp_encryption_key),
+            dummy_password_hash: OnceCell::new(),
+            config,
         }
     }
 }
 
 #[async_trait]
 impl AuthServiceTrait for AuthService {
+    #[instrument(skip_all)]
     async fn register_user(
         &self,
         username: &str,
         email: &str,
         password: &str,
-    ) -> Result<(User, String), AuthError> {
+        client: &ClientInfo,
+    ) -> Result<(User, AuthTokens), AuthError> {
         // Check if user already exists
         if self.user_service.find_user_by_email(email).await?.is_some() {
             return Err(AuthError::UserAlreadyExists);
@@ -56,8 +161,7 @@ impl AuthServiceTrait for AuthService {
         }
 
         // Hash the password
-        let password_hash = bcrypt::hash(password, bcrypt::DEFAULT_COST + 2)
-            .map_err(|_| AuthError::PasswordHashError)?;
+        let password_hash = self.password_hasher.hash(password).await?;
 
         // Create user
         let user = self
@@ -65,73 +169,899 @@ impl AuthServiceTrait for AuthService {
             .create_user(username, email, &password_hash)
             .await?;
 
-        // Generate JWT token
-        let token = self.generate_token(&user.id)?;
+        // The account is usable right away, so a failed email only means resending later
+        if let Err(err) = self.send_verification_email(&user).await {
+            warn!(error = %err, "failed to send verification email");
+        }
 
-        Ok((user, token))
+        // Generate JWT and refresh tokens
+        let tokens = self.issue_tokens(user.id, client).await?;
+
+        Ok((user, tokens))
     }
 
-    async fn login_user(&self, email: &str, password: &str) -> Result<(User, String), AuthError> {
+    #[instrument(skip_all)]
+    async fn login_user(
+        &self,
+        email: &str,
+        password: &str,
+        client: &ClientInfo,
+    ) -> Result<LoginOutcome, AuthError> {
+        let keys = LoginAttemptKeys::new(email, client);
+        self.check_login_attempts(&keys).await?;
+
         // Find user by email
+        let user = self.user_service.find_user_by_email(email).await?;
+
+        // Verify password, against a throwaway hash if there is no such user
+        let password_hash = match &user {
+            Some(user) => user.password_hash.as_str(),
+            None => self.dummy_password_hash().await?,
+        };
+        let password_valid = self.password_hasher.verify(password, password_hash).await?;
+
+        let Some(user) = user else {
+            self.record_login_failure(&keys).await?;
+            return Err(AuthError::UserNotFound);
+        };
+
+        if !password_valid {
+            self.record_login_failure(&keys).await?;
+            return Err(AuthError::InvalidPassword);
+        }
+
+        self.clear_login_failures(&keys).await?;
+
+        // The plain password is only available now, so this is when old hashes get upgraded
+        if self.password_hasher.needs_rehash(&user.password_hash)
+            && let Err(err) = self.set_password(user.id, password).await
+        {
+            warn!(error = %err, "failed to rehash password");
+        }
+
+        // No session is created until the second factor checks out too
+        if self.is_mfa_enabled(user.id).await? {
+            let mfa_token = self
+                .issue_one_time_token(
+                    user.id,
+                    TokenPurpose::MfaChallenge,
+                    self.config.mfa_challenge_ttl,
+                )
+                .await?;
+
+            return Ok(LoginOutcome::MfaRequired { mfa_token });
+        }
+
+        // Generate JWT and refresh tokens
+        let tokens = self.issue_tokens(user.id, client).await?;
+
+        Ok(LoginOutcome::Authenticated(user, tokens))
+    }
+
+    #[instrument(skip_all)]
+    async fn verify_mfa(
+        &self,
+        mfa_token: &str,
+        code: &str,
+        client: &ClientInfo,
+    ) -> Result<(User, AuthTokens), AuthError> {
+        let mfa_token_hash = opaque_token::hash(mfa_token);
+
+        // A wrong code leaves the challenge usable so a typo doesn't mean starting over
+        let challenge = self
+            .one_time_token_repository
+            .find_active(TokenPurpose::MfaChallenge, &mfa_token_hash)
+            .await?
+            .ok_or(AuthError::TokenValidationError)?;
+
+        let user = self
+            .user_service
+            .find_user_by_id(challenge.user_id)
+            .await?
+            .ok_or(AuthError::UserNotFound)?;
+
+        // Guessing codes counts against the account just like guessing passwords
+        let keys = LoginAttemptKeys::new(&user.email, client);
+        self.check_login_attempts(&keys).await?;
+
+        match self.verify_second_factor(user.id, code).await {
+            Err(AuthError::InvalidMfaCode) => {
+                self.record_login_failure(&keys).await?;
+                return Err(AuthError::InvalidMfaCode);
+            }
+            result => result?,
+        }
+
+        self.clear_login_failures(&keys).await?;
+
+        self.one_time_token_repository
+            .consume(TokenPurpose::MfaChallenge, &mfa_token_hash)
+            .await?
+            .ok_or(AuthError::TokenValidationError)?;
+
+        let tokens = self.issue_tokens(user.id, client).await?;
+
+        Ok((user, tokens))
+    }
+
+    #[instrument(skip_all)]
+    async fn enroll_totp(&self, user: &User) -> Result<TotpEnrollment, AuthError> {
+        let secret = totp::generate_secret();
+
+        self.mfa_repository
+            .save_pending_totp(user.id, &self.secret_cipher.encrypt(&secret)?)
+            .await?
+            .ok_or(AuthError::MfaAlreadyEnabled)?;
+
+        Ok(TotpEnrollment {
+            secret: totp::encode_secret(&secret),
+            otpauth_uri: totp::otpauth_uri(TOTP_ISSUER, &user.email, &secret),
+        })
+    }
+
+    #[instrument(skip_all, fields(user_id = %user_id))]
+    async fn confirm_totp(&self, user_id: Uuid, code: &str) -> Result<Vec<String>, AuthError> {
+        let credential = self
+            .mfa_repository
+            .find_totp(user_id)
+            .await?
+            .ok_or(AuthError::MfaNotEnabled)?;
+
+        if credential.is_enabled() {
+            return Err(AuthError::MfaAlreadyEnabled);
+        }
+
+        let secret = self.secret_cipher.decrypt(&credential.secret_ciphertext)?;
+        let step = totp::verify(&secret, code, Utc::now()).ok_or(AuthError::InvalidMfaCode)?;
+
+        let recovery_codes = generate_recovery_codes();
+        let confirmed = self
+            .mfa_repository
+            .confirm_totp(user_id, step, &hash_recovery_codes(&recovery_codes))
+            .await?;
+
+        // Another request confirmed it first
+        if !confirmed {
+            return Err(AuthError::MfaAlreadyEnabled);
+        }
+
+        Ok(recovery_codes)
+    }
+
+    #[instrument(skip_all, fields(user_id = %user_id))]
+    async fn disable_totp(&self, user_id: Uuid, code: &str) -> Result<(), AuthError> {
+        self.verify_second_factor(user_id, code).await?;
+
+        self.mfa_repository.delete(user_id).await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(user_id = %user_id))]
+    async fn regenerate_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code: &str,
+    ) -> Result<Vec<String>, AuthError> {
+        self.verify_second_factor(user_id, code).await?;
+
+        let recovery_codes = generate_recovery_codes();
+        self.mfa_repository
+            .replace_recovery_codes(user_id, &hash_recovery_codes(&recovery_codes))
+            .await?;
+
+        Ok(recovery_codes)
+    }
+
+    #[instrument(skip_all)]
+    async fn get_current_user(
+        &self,
+        user: User,
+        credential: &Credential,
+    ) -> Result<(User, Option<String>), AuthError> {
+        // Personal access tokens can't be exchanged for a session token
+        let token = match credential {
+            Credential::Session(session_id) => Some(self.generate_token(&user.id, session_id)?),
+            Credential::PersonalAccessToken { .. } => None,
+        };
+        Ok((user, token))
+    }
+
+    #[instrument(skip_all)]
+    async fn validate_token(&self, token: &str) -> Result<Principal, AuthError> {
+        if token.starts_with(PERSONAL_ACCESS_TOKEN_PREFIX) {
+            return self.validate_personal_access_token(token).await;
+        }
+
+        let (user_id, session_id) = self.decode_access_token(token)?;
+
+        if self.is_session_revoked(user_id, session_id).await? {
+            return Err(AuthError::TokenValidationError);
+        }
+
+        Ok(Principal {
+            user_id,
+            credential: Credential::Session(session_id),
+        })
+    }
+
+    fn token_subject(&self, token: &str) -> Option<Uuid> {
+        if token.starts_with(PERSONAL_ACCESS_TOKEN_PREFIX) {
+            return None;
+        }
+
+        self.decode_access_token(token)
+            .ok()
+            .map(|(user_id, _)| user_id)
+    }
+
+    fn jwks(&self) -> &JwkSet {
+        self.config.jwt_keys.jwks()
+    }
+
+    #[instrument(skip_all)]
+    async fn refresh_tokens(
+        &self,
+        refresh_token: &str,
+        client: &ClientInfo,
+    ) -> Result<(User, AuthTokens), AuthError> {
+        let token = self
+            .refresh_token_repository
+            .find_by_hash(&opaque_token::hash(refresh_token))
+            .await?
+            .ok_or(AuthError::TokenValidationError)?;
+
+        if token.revoked_at.is_some() || token.expires_at <= Utc::now() {
+            return Err(AuthError::TokenValidationError);
+        }
+
+        // A rotated token coming back means it leaked, so the whole session goes
+        if token.rotated_at.is_some() {
+            self.revoke_session(token.user_id, token.family_id).await?;
+            return Err(AuthError::RefreshTokenReused);
+        }
+
+        let new_refresh_token = opaque_token::generate();
+        let rotated = self
+            .refresh_token_repository
+            .rotate(
+                token.id,
+                &opaque_token::hash(&new_refresh_token),
+                Utc::now() + self.config.refresh_token_ttl,
+            )
+            .await?;
+
+        // Lost the race against another request presenting the same token
+        if rotated.is_none() {
+            self.revoke_session(token.user_id, token.family_id).await?;
+            return Err(AuthError::RefreshTokenReused);
+        }
+
+        self.session_repository
+            .touch(token.family_id, client)
+            .await?;
+
         let user = self
             .user_service
-            .find_user_by_email(email)
+            .find_user_by_id(token.user_id)
             .await?
             .ok_or(AuthError::UserNotFound)?;
 
-        // Verify password
-        let password_valid = bcrypt::verify(password, &user.password_hash)
-            .map_err(|_| AuthError::PasswordHashError)?;
+        let tokens = AuthTokens {
+            access_token: self.generate_token(&user.id, &token.family_id)?,
+            refresh_token: new_refresh_token,
+        };
+
+        Ok((user, tokens))
+    }
+
+    #[instrument(skip_all, fields(user_id = %user_id))]
+    async fn find_sessions(&self, user_id: Uuid) -> Result<Vec<Session>, AuthError> {
+        self.session_repository.find_active_by_user(user_id).await
+    }
+
+    #[instrument(skip_all, fields(user_id = %user_id, session_id = %session_id))]
+    async fn revoke_session(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, AuthError> {
+        let revoked = self.session_repository.revoke(user_id, session_id).await?;
+        if revoked {
+            self.session_cache.insert(session_id, true);
+        }
+
+        Ok(revoked)
+    }
+
+    #[instrument(skip_all, fields(user_id = %user_id))]
+    async fn revoke_all_sessions(&self, user_id: Uuid) -> Result<u64, AuthError> {
+        self.revoke_sessions(user_id, None).await
+    }
+
+    #[instrument(skip_all, fields(user_id = %user.id))]
+    async fn verify_password(&self, user: &User, password: &str) -> Result<(), AuthError> {
+        let password_valid = self
+            .password_hasher
+            .verify(password, &user.password_hash)
+            .await?;
 
         if !password_valid {
             return Err(AuthError::InvalidPassword);
         }
 
-        // Generate JWT token
-        let token = self.generate_token(&user.id)?;
+        Ok(())
+    }
 
-        Ok((user, token))
+    #[instrument(skip_all, fields(current_session_id = %current_session_id))]
+    async fn change_password(
+        &self,
+        user: &User,
+        current_session_id: Uuid,
+        current_password: &str,
+        new_password: &str,
+    ) -> Result<(), AuthError> {
+        self.verify_password(user, current_password).await?;
+
+        self.set_password(user.id, new_password).await?;
+
+        // Everything except the device that made the change has to log in again, and
+        // API tokens minted with the old password stop working
+        self.revoke_sessions(user.id, Some(current_session_id))
+            .await?;
+        self.personal_access_token_repository
+            .revoke_all(user.id)
+            .await?;
+
+        Ok(())
     }
 
-    async fn get_current_user(&self, user: User) -> Result<(User, String), AuthError> {
-        let token = self.generate_token(&user.id)?;
-        Ok((user, token))
+    #[instrument(skip_all)]
+    async fn request_password_reset(&self, email: &str) -> Result<(), AuthError> {
+        // Unknown addresses succeed silently so the endpoint can't be used to probe accounts
+        let Some(user) = self.user_service.find_user_by_email(email).await? else {
+            return Ok(());
+        };
+
+        let token = opaque_token::generate();
+        let email = Email {
+            to: user.email,
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Hi {},\n\n\
+                 Use the link below to choose a new password. It expires in {} minutes \
+                 and can only be used once.\n\n\
+                 {}/reset-password?token={}\n\n\
+                 If you didn't ask for this, you can ignore this email.",
+                user.username,
+                self.config.password_reset_ttl.num_minutes(),
+                self.config.app_url.trim_end_matches('/'),
+                token
+            ),
+        };
+
+        // Storing the token and sending the mail happen after responding, so a known
+        // address takes as long as an unknown one and a mail failure can't give it away
+        let one_time_tokens = Arc::clone(&self.one_time_token_repository);
+        let mailer = Arc::clone(&self.mailer);
+        let ttl = self.config.password_reset_ttl;
+        self.background_tasks.spawn(
+            "password reset email",
+            async move {
+                let result = async {
+                    store_one_time_token(
+                        one_time_tokens.as_ref(),
+                        user.id,
+                        TokenPurpose::PasswordReset,
+                        &token,
+                        ttl,
+                    )
+                    .await?;
+                    mailer
+                        .send(&email)
+                        .await
+                        .map_err(AuthError::MailDeliveryError)
+                }
+                .await;
+
+                if let Err(err) = result {
+                    warn!(error = %err, user_id = %user.id, "failed to send password reset email");
+                }
+            }
+            .in_current_span(),
+        );
+
+        Ok(())
     }
 
-    async fn validate_token(&self, token: &str) -> Result<Uuid, AuthError> {
-        let data = decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(self.jwt_secret.as_ref()),
-            &Validation::default(),
-        )
-        .map_err(|_| AuthError::TokenValidationError)?;
+    #[instrument(skip_all)]
+    async fn reset_password(&self, token: &str, new_password: &str) -> Result<(), AuthError> {
+        let token = self
+            .one_time_token_repository
+            .consume(TokenPurpose::PasswordReset, &opaque_token::hash(token))
+            .await?
+            .ok_or(AuthError::TokenValidationError)?;
+
+        self.set_password(token.user_id, new_password).await?;
+
+        // Whoever knew the old password is logged out everywhere, API tokens included
+        self.revoke_sessions(token.user_id, None).await?;
+        self.personal_access_token_repository
+            .revoke_all(token.user_id)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn send_verification_email(&self, user: &User) -> Result<(), AuthError> {
+        let token = self
+            .issue_one_time_token(
+                user.id,
+                TokenPurpose::EmailVerification,
+                self.config.email_verification_ttl,
+            )
+            .await?;
+
+        let email = Email {
+            to: user.email.clone(),
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Hi {},\n\n\
+                 Please confirm that this is your email address by opening the link below. \
+                 It expires in {} hours.\n\n\
+                 {}/api/auth/verify?token={}\n\n\
+                 If you didn't create an account, you can ignore this email.",
+                user.username,
+                self.config.email_verification_ttl.num_hours(),
+                self.config.app_url.trim_end_matches('/'),
+                token
+            ),
+        };
+
+        self.mailer
+            .send(&email)
+            .await
+            .map_err(AuthError::MailDeliveryError)
+    }
 
-        let user_id =
-            Uuid::parse_str(&data.claims.sub).map_err(|_| AuthError::TokenValidationError)?;
+    #[instrument(skip_all)]
+    async fn verify_email(&self, token: &str) -> Result<User, AuthError> {
+        let token = self
+            .one_time_token_repository
+            .consume(TokenPurpose::EmailVerification, &opaque_token::hash(token))
+            .await?
+            .ok_or(AuthError::TokenValidationError)?;
+
+        self.user_service
+            .mark_email_verified(token.user_id)
+            .await?
+            .ok_or(AuthError::UserNotFound)
+    }
 
-        Ok(user_id)
+    #[instrument(skip_all)]
+    async fn purge_login_attempts(&self) -> Result<u64, AuthError> {
+        let cutoff = Utc::now() - self.config.login_lockout_duration;
+        self.login_attempt_repository.delete_stale(cutoff).await
+    }
+
+    #[instrument(skip_all, fields(user_id = %user_id))]
+    async fn create_personal_access_token(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        scopes: &[Scope],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(PersonalAccessToken, String), AuthError> {
+        let secret = format!(
+            "{}{}",
+            PERSONAL_ACCESS_TOKEN_PREFIX,
+            opaque_token::generate()
+        );
+        // Enough of the secret to recognise the token in a list, not to guess it
+        let token_prefix = &secret[..PERSONAL_ACCESS_TOKEN_PREFIX.len() + 8];
+        let scopes: Vec<String> = scopes
+            .iter()
+            .map(|scope| scope.as_str().to_string())
+            .collect();
+
+        let token = self
+            .personal_access_token_repository
+            .create(
+                user_id,
+                name,
+                token_prefix,
+                &opaque_token::hash(&secret),
+                &scopes,
+                expires_at,
+            )
+            .await?;
+
+        Ok((token, secret))
+    }
+
+    #[instrument(skip_all, fields(user_id = %user_id))]
+    async fn find_personal_access_tokens(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<PersonalAccessToken>, AuthError> {
+        self.personal_access_token_repository
+            .find_by_user(user_id)
+            .await
+    }
+
+    #[instrument(skip_all, fields(user_id = %user_id, token_id = %token_id))]
+    async fn revoke_personal_access_token(
+        &self,
+        user_id: Uuid,
+        token_id: Uuid,
+    ) -> Result<bool, AuthError> {
+        self.personal_access_token_repository
+            .revoke(user_id, token_id)
+            .await
     }
 }
 
 impl AuthService {
-    fn generate_token(&self, user_id: &uuid::Uuid) -> Result<String, AuthError> {
+    // Checks the signature and claims of a session token; revocation is the caller's concern
+    fn decode_access_token(&self, token: &str) -> Result<(Uuid, Uuid), AuthError> {
+        let header = decode_header(token).map_err(invalid_token)?;
+        let (algorithm, decoding_key) = header
+            .kid
+            .as_deref()
+            .and_then(|kid| self.config.jwt_keys.decoding_key(kid))
+            .ok_or(AuthError::TokenValidationError)?;
+
+        let mut validation = Validation::new(algorithm);
+        validation.set_issuer(&[&self.config.jwt_issuer]);
+        validation.set_audience(&[&self.config.jwt_audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let data = decode::<Claims>(token, decoding_key, &validation).map_err(invalid_token)?;
+
+        let user_id = Uuid::parse_str(&data.claims.sub).map_err(invalid_token)?;
+        let session_id = Uuid::parse_str(&data.claims.sid).map_err(invalid_token)?;
+
+        Ok((user_id, session_id))
+    }
+
+    // Starts a new session, whose id is the family of its refresh tokens
+    async fn issue_tokens(
+        &self,
+        user_id: Uuid,
+        client: &ClientInfo,
+    ) -> Result<AuthTokens, AuthError> {
+        let session = self.session_repository.create(user_id, client).await?;
+
+        let refresh_token = opaque_token::generate();
+        self.refresh_token_repository
+            .create(
+                user_id,
+                session.id,
+                &opaque_token::hash(&refresh_token),
+                Utc::now() + self.config.refresh_token_ttl,
+            )
+            .await?;
+
+        Ok(AuthTokens {
+            access_token: self.generate_token(&user_id, &session.id)?,
+            refresh_token,
+        })
+    }
+
+    // Only the most recent token of each purpose stays valid
+    async fn issue_one_time_token(
+        &self,
+        user_id: Uuid,
+        purpose: TokenPurpose,
+        ttl: Duration,
+    ) -> Result<String, AuthError> {
+        let token = opaque_token::generate();
+        store_one_time_token(
+            self.one_time_token_repository.as_ref(),
+            user_id,
+            purpose,
+            &token,
+            ttl,
+        )
+        .await?;
+
+        Ok(token)
+    }
+
+    async fn check_login_attempts(&self, keys: &LoginAttemptKeys) -> Result<(), AuthError> {
+        let now = Utc::now();
+
+        for attempt in self.login_attempt_repository.find(&keys.all()).await? {
+            let is_account = attempt.key == keys.account;
+
+            if let Some(locked_until) = attempt.locked_until.filter(|until| *until > now) {
+                let retry_after = locked_until - now;
+                return Err(if is_account {
+                    AuthError::AccountLocked { retry_after }
+                } else {
+                    AuthError::TooManyAttempts { retry_after }
+                });
+            }
+
+            let free_attempts = if is_account {
+                ACCOUNT_FREE_ATTEMPTS
+            } else {
+                IP_FREE_ATTEMPTS
+            };
+            let retry_at =
+                attempt.last_failed_at + backoff_delay(attempt.failed_count, free_attempts);
+            if retry_at > now {
+                return Err(AuthError::TooManyAttempts {
+                    retry_after: retry_at - now,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn record_login_failure(&self, keys: &LoginAttemptKeys) -> Result<(), AuthError> {
         let now = Utc::now();
-        let exp = (now + Duration::hours(1)).timestamp() as usize;
+        let window_start = now - self.config.login_lockout_duration;
+
+        for key in keys.all() {
+            let attempt = self
+                .login_attempt_repository
+                .record_failure(&key, window_start)
+                .await?;
+
+            let max_failed_attempts = if key == keys.account {
+                self.config.login_max_failed_attempts
+            } else {
+                self.config.login_ip_max_failed_attempts
+            };
+            if attempt.failed_count >= max_failed_attempts {
+                self.login_attempt_repository
+                    .lock(&key, now + self.config.login_lockout_duration)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Only the account is cleared; an attacker could otherwise reset their IP's
+    // count by logging in to an account of their own
+    async fn clear_login_failures(&self, keys: &LoginAttemptKeys) -> Result<(), AuthError> {
+        self.login_attempt_repository.clear(&keys.account).await
+    }
+
+    // Created on first use, with the same parameters as real hashes
+    async fn dummy_password_hash(&self) -> Result<&str, AuthError> {
+        let password_hash = self
+            .dummy_password_hash
+            .get_or_try_init(|| async {
+                self.password_hasher.hash(&opaque_token::generate()).await
+            })
+            .await?;
+
+        Ok(password_hash)
+    }
+
+    async fn is_mfa_enabled(&self, user_id: Uuid) -> Result<bool, AuthError> {
+        let credential = self.mfa_repository.find_totp(user_id).await?;
+
+        Ok(credential.is_some_and(|credential| credential.is_enabled()))
+    }
+
+    // Accepts either a current TOTP code or an unused recovery code
+    async fn verify_second_factor(&self, user_id: Uuid, code: &str) -> Result<(), AuthError> {
+        let credential = self
+            .mfa_repository
+            .find_totp(user_id)
+            .await?
+            .filter(|credential| credential.is_enabled())
+            .ok_or(AuthError::MfaNotEnabled)?;
+
+        let code = code.trim();
+        let accepted = if totp::is_code_format(code) {
+            let secret = self.secret_cipher.decrypt(&credential.secret_ciphertext)?;
+            match totp::verify(&secret, code, Utc::now()) {
+                Some(step) => self.mfa_repository.use_totp_step(user_id, step).await?,
+                None => false,
+            }
+        } else {
+            self.mfa_repository
+                .use_recovery_code(user_id, &hash_recovery_code(code))
+                .await?
+        };
+
+        if !accepted {
+            return Err(AuthError::InvalidMfaCode);
+        }
+
+        Ok(())
+    }
+
+    async fn set_password(&self, user_id: Uuid, password: &str) -> Result<(), AuthError> {
+        let password_hash = self.password_hasher.hash(password).await?;
+
+        self.user_service
+            .update_password(user_id, &password_hash)
+            .await?
+            .ok_or(AuthError::UserNotFound)?;
+
+        Ok(())
+    }
+
+    async fn revoke_sessions(
+        &self,
+        user_id: Uuid,
+        except_session_id: Option<Uuid>,
+    ) -> Result<u64, AuthError> {
+        let session_ids = self
+            .session_repository
+            .revoke_all(user_id, except_session_id)
+            .await?;
+        for session_id in &session_ids {
+            self.session_cache.insert(*session_id, true);
+        }
+
+        Ok(session_ids.len() as u64)
+    }
+
+    async fn validate_personal_access_token(&self, token: &str) -> Result<Principal, AuthError> {
+        let token = self
+            .personal_access_token_repository
+            .find_by_hash(&opaque_token::hash(token))
+            .await?
+            .ok_or(AuthError::TokenValidationError)?;
+
+        let now = Utc::now();
+        if token.revoked_at.is_some()
+            || token.expires_at.is_some_and(|expires_at| expires_at <= now)
+        {
+            return Err(AuthError::TokenValidationError);
+        }
+
+        let used_before = now - Duration::seconds(TOKEN_USAGE_RESOLUTION_SECS);
+        if token
+            .last_used_at
+            .is_none_or(|last_used_at| last_used_at < used_before)
+        {
+            self.personal_access_token_repository
+                .touch(token.id, used_before)
+                .await?;
+        }
+
+        Ok(Principal {
+            user_id: token.user_id,
+            credential: Credential::PersonalAccessToken {
+                token_id: token.id,
+                scopes: token.scopes(),
+            },
+        })
+    }
+
+    async fn is_session_revoked(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, AuthError> {
+        if let Some(revoked) = self.session_cache.is_revoked(session_id) {
+            return Ok(revoked);
+        }
+
+        let revoked = match self.session_repository.find_by_id(session_id).await? {
+            Some(session) => session.revoked_at.is_some() || session.user_id != user_id,
+            None => true,
+        };
+        self.session_cache.insert(session_id, revoked);
+
+        Ok(revoked)
+    }
+
+    fn generate_token(&self, user_id: &Uuid, session_id: &Uuid) -> Result<String, AuthError> {
+        let now = Utc::now();
+        let exp = (now + self.config.access_token_ttl).timestamp() as usize;
         let iat = now.timestamp() as usize;
 
         let claims = Claims {
+            iss: self.config.jwt_issuer.clone(),
+            aud: self.config.jwt_audience.clone(),
             sub: user_id.to_string(),
+            sid: session_id.to_string(),
+            jti: Uuid::new_v4().to_string(),
             exp,
             iat,
         };
 
-        let token = encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(self.jwt_secret.as_ref()),
-        )
-        .map_err(|_| AuthError::TokenGenerationError)?;
+        let token = self.config.jwt_keys.encode(&claims).map_err(|err| {
+            error!(error = %err, "failed to sign access token");
+            AuthError::TokenGenerationError
+        })?;
 
         Ok(token)
     }
 }
+
+// Keys failed logins are counted under
+struct LoginAttemptKeys {
+    account: String,
+    ip: Option<String>,
+}
+
+impl LoginAttemptKeys {
+    fn new(email: &str, client: &ClientInfo) -> Self {
+        Self {
+            account: format!("email:{}", email.trim().to_lowercase()),
+            ip: client
+                .ip_address
+                .as_ref()
+                .map(|ip_address| format!("ip:{}", ip_address)),
+        }
+    }
+
+    fn all(&self) -> Vec<String> {
+        std::iter::once(self.account.clone())
+            .chain(self.ip.clone())
+            .collect()
+    }
+}
+
+// Replaces any earlier token the user has for the same purpose
+async fn store_one_time_token(
+    repository: &dyn OneTimeTokenRepositoryTrait,
+    user_id: Uuid,
+    purpose: TokenPurpose,
+    token: &str,
+    ttl: Duration,
+) -> Result<(), AuthError> {
+    repository.invalidate_for_user(user_id, purpose).await?;
+    repository
+        .create(
+            user_id,
+            purpose,
+            &opaque_token::hash(token),
+            Utc::now() + ttl,
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn backoff_delay(failed_count: i32, free_attempts: i32) -> Duration {
+    let excess = failed_count - free_attempts;
+    if excess <= 0 {
+        return Duration::zero();
+    }
+
+    Duration::seconds(MAX_BACKOFF_SECS.min(1 << (excess - 1).min(16)))
+}
+
+// 80 bits each, grouped like xxxx-xxxx-xxxx-xxxx so they are easy to copy down
+fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 10];
+            OsRng.fill_bytes(&mut bytes);
+            let encoded = BASE32_NOPAD.encode(&bytes).to_ascii_lowercase();
+
+            encoded
+                .as_bytes()
+                .chunks(4)
+                .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .collect()
+}
+
+fn hash_recovery_codes(codes: &[String]) -> Vec<String> {
+    codes.iter().map(|code| hash_recovery_code(code)).collect()
+}
+
+// Dashes, spaces and case don't matter when a code is typed back in
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    opaque_token::hash(&normalized)
+}
+
+// Bad tokens are routine, so the reason is only kept at debug level
+fn invalid_token(err: impl fmt::Display) -> AuthError {
+    debug!(error = %err, "rejected access token");
+    AuthError::TokenValidationError
+}
@@ -0,0 +1,200 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+};
+use rsa::{
+    RsaPrivateKey, RsaPublicKey,
+    pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey},
+    pkcs8::{DecodePrivateKey, DecodePublicKey},
+    traits::PublicKeyParts,
+};
+use serde::Serialize;
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+pub use jsonwebtoken::jwk::JwkSet;
+
+#[derive(Debug)]
+pub struct JwtKeyError(pub String);
+
+impl fmt::Display for JwtKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid JWT key: {}", self.0)
+    }
+}
+
+impl std::error::Error for JwtKeyError {}
+
+// Signs access tokens with one private key and accepts tokens signed by any
+// loaded key, so keys can be rotated without logging anyone out
+pub struct JwtKeys {
+    signing_kid: String,
+    signing_algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_keys: HashMap<String, (Algorithm, DecodingKey)>,
+    jwks: JwkSet,
+}
+
+impl JwtKeys {
+    // Loads every `<kid>.pem` in the directory. Each may be an RSA or Ed25519
+    // private or public key; the one named by signing_kid has to be private.
+    pub fn load_dir(dir: impl AsRef<Path>, signing_kid: &str) -> Result<Self, JwtKeyError> {
+        let dir = dir.as_ref();
+        let entries = fs::read_dir(dir)
+            .map_err(|err| JwtKeyError(format!("cannot read {}: {}", dir.display(), err)))?;
+
+        let mut signing_key = None;
+        let mut decoding_keys = HashMap::new();
+        let mut jwks = Vec::new();
+
+        for entry in entries {
+            let path = entry.map_err(|err| JwtKeyError(err.to_string()))?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("pem") {
+                continue;
+            }
+            let Some(kid) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let pem = fs::read_to_string(&path)
+                .map_err(|err| JwtKeyError(format!("cannot read {}: {}", path.display(), err)))?;
+            let key = ParsedKey::from_pem(&pem)
+                .ok_or_else(|| JwtKeyError(format!("{} is not an RSA or Ed25519 key", kid)))?;
+            let jwk = key.to_jwk(kid);
+            let decoding_key = DecodingKey::from_jwk(&jwk)
+                .map_err(|err| JwtKeyError(format!("{}: {}", kid, err)))?;
+
+            if kid == signing_kid {
+                if !key.is_private {
+                    return Err(JwtKeyError(format!(
+                        "signing key {} must be a private key",
+                        kid
+                    )));
+                }
+                let encoding_key = match key.algorithm {
+                    Algorithm::EdDSA => EncodingKey::from_ed_pem(pem.as_bytes()),
+                    _ => EncodingKey::from_rsa_pem(pem.as_bytes()),
+                }
+                .map_err(|err| JwtKeyError(format!("{}: {}", kid, err)))?;
+                signing_key = Some((key.algorithm, encoding_key));
+            }
+
+            decoding_keys.insert(kid.to_string(), (key.algorithm, decoding_key));
+            jwks.push(jwk);
+        }
+
+        let (signing_algorithm, encoding_key) = signing_key.ok_or_else(|| {
+            JwtKeyError(format!(
+                "signing key {}.pem not found in {}",
+                signing_kid,
+                dir.display()
+            ))
+        })?;
+
+        // Stable order so the published set doesn't shuffle between restarts
+        jwks.sort_by(|a, b| a.common.key_id.cmp(&b.common.key_id));
+
+        Ok(Self {
+            signing_kid: signing_kid.to_string(),
+            signing_algorithm,
+            encoding_key,
+            decoding_keys,
+            jwks: JwkSet { keys: jwks },
+        })
+    }
+
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        let header = Header {
+            kid: Some(self.signing_kid.clone()),
+            ..Header::new(self.signing_algorithm)
+        };
+
+        jsonwebtoken::encode(&header, claims, &self.encoding_key)
+    }
+
+    // The algorithm comes from our key, never from the token header
+    pub fn decoding_key(&self, kid: &str) -> Option<(Algorithm, &DecodingKey)> {
+        self.decoding_keys
+            .get(kid)
+            .map(|(algorithm, key)| (*algorithm, key))
+    }
+
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+struct ParsedKey {
+    algorithm: Algorithm,
+    params: AlgorithmParameters,
+    is_private: bool,
+}
+
+impl ParsedKey {
+    fn from_pem(pem: &str) -> Option<Self> {
+        if let Ok(key) =
+            RsaPrivateKey::from_pkcs8_pem(pem).or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+        {
+            return Some(Self::rsa(&key.to_public_key(), true));
+        }
+        if let Ok(key) =
+            RsaPublicKey::from_public_key_pem(pem).or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+        {
+            return Some(Self::rsa(&key, false));
+        }
+        if let Ok(key) = SigningKey::from_pkcs8_pem(pem) {
+            return Some(Self::ed25519(&key.verifying_key(), true));
+        }
+        if let Ok(key) = VerifyingKey::from_public_key_pem(pem) {
+            return Some(Self::ed25519(&key, false));
+        }
+
+        None
+    }
+
+    fn rsa(key: &RsaPublicKey, is_private: bool) -> Self {
+        Self {
+            algorithm: Algorithm::RS256,
+            params: AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+                e: URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+            }),
+            is_private,
+        }
+    }
+
+    fn ed25519(key: &VerifyingKey, is_private: bool) -> Self {
+        Self {
+            algorithm: Algorithm::EdDSA,
+            params: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(key.as_bytes()),
+            }),
+            is_private,
+        }
+    }
+
+    // Only public parameters ever end up in a JWK
+    fn to_jwk(&self, kid: &str) -> Jwk {
+        let key_algorithm = match self.algorithm {
+            Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+            _ => KeyAlgorithm::RS256,
+        };
+
+        Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.to_string()),
+                ..Default::default()
+            },
+            algorithm: self.params.clone(),
+        }
+    }
+}
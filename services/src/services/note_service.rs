@@ -1,54 +1,248 @@
-use crate::{Note, repositories::traits::NoteRepositoryTrait, services::traits::NoteServiceTrait};
+use crate::{
+    Note,
+    errors::NoteError,
+    models::{
+        DiffLine, DiffOp, NoteListFilter, NotePage, NoteRevision, NoteSearchFilter, NoteSearchPage,
+        RevisionDiff, RevisionRetention, tag::normalize_tags,
+    },
+    repositories::traits::NoteRepositoryTrait,
+    services::traits::NoteServiceTrait,
+};
 use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use similar::{ChangeTag, TextDiff};
 use std::sync::Arc;
+use tracing::instrument;
 use uuid::Uuid;
 
 pub struct NoteService {
     note_repository: Arc<dyn NoteRepositoryTrait>,
+    revision_retention: RevisionRetention,
 }
 
 impl NoteService {
-    pub fn new(note_repository: Arc<dyn NoteRepositoryTrait>) -> Self {
-        Self { note_repository }
+    pub fn new(
+        note_repository: Arc<dyn NoteRepositoryTrait>,
+        revision_retention: RevisionRetention,
+    ) -> Self {
+        Self {
+            note_repository,
+            revision_retention,
+        }
     }
+
+    async fn prune_revisions(&self, note_id: Uuid) -> Result<(), NoteError> {
+        let RevisionRetention { max_count, max_age } = self.revision_retention;
+        if max_count.is_none() && max_age.is_none() {
+            return Ok(());
+        }
+
+        let created_before = max_age.map(|max_age| Utc::now() - max_age);
+        self.note_repository
+            .prune_revisions(note_id, max_count, created_before)
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn diff_lines(from: &str, to: &str) -> Vec<DiffLine> {
+    // Terminate both texts so an unterminated last line still compares equal
+    let from = format!("{}\n", from.trim_end_matches('\n'));
+    let to = format!("{}\n", to.trim_end_matches('\n'));
+
+    TextDiff::from_lines(&from, &to)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            op: match change.tag() {
+                ChangeTag::Equal => DiffOp::Equal,
+                ChangeTag::Insert => DiffOp::Insert,
+                ChangeTag::Delete => DiffOp::Delete,
+            },
+            text: change.value().trim_end_matches(['\r', '\n']).to_string(),
+        })
+        .collect()
 }
 
 #[async_trait]
 impl NoteServiceTrait for NoteService {
+    #[instrument(skip_all, fields(user_id = %user_id))]
     async fn create_note(
         &self,
         user_id: Uuid,
         title: &str,
         content: &str,
-    ) -> Result<Note, sqlx::Error> {
-        self.note_repository.create(user_id, title, content).await
+        tags: &[String],
+    ) -> Result<Note, NoteError> {
+        let tags = normalize_tags(tags);
+        self.note_repository
+            .create(user_id, title, content, &tags)
+            .await
     }
 
+    #[instrument(skip_all, fields(note_id = %note_id, user_id = %user_id))]
     async fn find_note_by_id(
         &self,
         note_id: Uuid,
         user_id: Uuid,
-    ) -> Result<Option<Note>, sqlx::Error> {
+    ) -> Result<Option<Note>, NoteError> {
         self.note_repository.find_note_by_id(note_id, user_id).await
     }
 
-    async fn find_notes_by_user_id(&self, user_id: Uuid) -> Result<Vec<Note>, sqlx::Error> {
-        self.note_repository.find_all_notes(user_id).await
+    #[instrument(skip_all)]
+    async fn find_notes(&self, mut filter: NoteListFilter) -> Result<NotePage, NoteError> {
+        filter.tags = normalize_tags(&filter.tags);
+        self.note_repository.find_all_notes(&filter).await
+    }
+
+    #[instrument(skip_all)]
+    async fn search_notes(&self, filter: NoteSearchFilter) -> Result<NoteSearchPage, NoteError> {
+        self.note_repository.search(&filter).await
     }
 
+    #[instrument(skip_all, fields(note_id = %note_id, user_id = %user_id))]
     async fn update_note(
         &self,
         note_id: Uuid,
         user_id: Uuid,
         title: Option<&str>,
         content: Option<&str>,
-    ) -> Result<Option<Note>, sqlx::Error> {
+        tags: Option<&[String]>,
+        expected_versions: Option<&[i32]>,
+    ) -> Result<Note, NoteError> {
+        let tags = tags.map(normalize_tags);
+        let note = self
+            .note_repository
+            .update(
+                note_id,
+                user_id,
+                title,
+                content,
+                tags.as_deref(),
+                expected_versions,
+            )
+            .await?;
+
+        if let Some(note) = note {
+            self.prune_revisions(note_id).await?;
+            return Ok(note);
+        }
+
+        // The version check is part of the UPDATE itself, so a missed row is only
+        // a conflict if the note still exists
+        if expected_versions.is_some()
+            && self
+                .note_repository
+                .find_note_by_id(note_id, user_id)
+                .await?
+                .is_some()
+        {
+            return Err(NoteError::VersionMismatch);
+        }
+
+        Err(NoteError::NotFound)
+    }
+
+    #[instrument(skip_all, fields(note_id = %note_id, user_id = %user_id))]
+    async fn delete_note(&self, note_id: Uuid, user_id: Uuid) -> Result<Option<Note>, NoteError> {
+        self.note_repository.delete(note_id, user_id).await
+    }
+
+    #[instrument(skip_all, fields(user_id = %user_id))]
+    async fn find_deleted_notes(&self, user_id: Uuid) -> Result<Vec<Note>, NoteError> {
+        self.note_repository.find_deleted_notes(user_id).await
+    }
+
+    #[instrument(skip_all, fields(note_id = %note_id, user_id = %user_id))]
+    async fn restore_note(&self, note_id: Uuid, user_id: Uuid) -> Result<Option<Note>, NoteError> {
+        self.note_repository.restore(note_id, user_id).await
+    }
+
+    #[instrument(skip_all, fields(user_id = %user_id))]
+    async fn empty_trash(&self, user_id: Uuid) -> Result<u64, NoteError> {
+        self.note_repository.empty_trash(user_id).await
+    }
+
+    #[instrument(skip_all)]
+    async fn purge_trash(&self, retention: Duration) -> Result<u64, NoteError> {
+        let cutoff = Utc::now() - retention;
+        self.note_repository.purge_deleted_before(cutoff).await
+    }
+
+    #[instrument(skip_all, fields(note_id = %note_id, user_id = %user_id))]
+    async fn find_revisions(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<NoteRevision>, NoteError> {
+        self.note_repository.find_revisions(note_id, user_id).await
+    }
+
+    #[instrument(skip_all, fields(note_id = %note_id, user_id = %user_id))]
+    async fn find_revision(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+        revision_number: i32,
+    ) -> Result<Option<NoteRevision>, NoteError> {
         self.note_repository
-            .update(note_id, user_id, title, content)
+            .find_revision(note_id, user_id, revision_number)
             .await
     }
 
-    async fn delete_note(&self, note_id: Uuid) -> Result<Option<Note>, sqlx::Error> {
-        self.note_repository.delete(note_id).await
+    #[instrument(skip_all, fields(note_id = %note_id, user_id = %user_id))]
+    async fn restore_revision(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+        revision_number: i32,
+    ) -> Result<Option<Note>, NoteError> {
+        let Some(revision) = self
+            .note_repository
+            .find_revision(note_id, user_id, revision_number)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        // Restoring goes through a regular update so it is recorded as a new revision
+        let note = self
+            .update_note(
+                note_id,
+                user_id,
+                Some(&revision.title),
+                Some(&revision.content),
+                None,
+                None,
+            )
+            .await?;
+
+        Ok(Some(note))
+    }
+
+    #[instrument(skip_all, fields(note_id = %note_id, user_id = %user_id))]
+    async fn diff_revisions(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+        from: i32,
+        to: i32,
+    ) -> Result<Option<RevisionDiff>, NoteError> {
+        let from = self
+            .note_repository
+            .find_revision(note_id, user_id, from)
+            .await?;
+        let to = self
+            .note_repository
+            .find_revision(note_id, user_id, to)
+            .await?;
+
+        let (Some(from), Some(to)) = (from, to) else {
+            return Ok(None);
+        };
+
+        let lines = diff_lines(&from.content, &to.content);
+
+        Ok(Some(RevisionDiff { from, to, lines }))
     }
 }
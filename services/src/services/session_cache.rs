@@ -0,0 +1,43 @@
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+// Entries beyond this count trigger a sweep of expired ones
+const SWEEP_THRESHOLD: usize = 10_000;
+
+// Remembers whether a session was revoked so access tokens can be checked
+// without a database round-trip on every request. Revocations made by this
+// process are visible immediately; those made elsewhere within `ttl`.
+pub struct SessionCache {
+    ttl: Duration,
+    entries: RwLock<HashMap<Uuid, (bool, Instant)>>,
+}
+
+impl SessionCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_revoked(&self, session_id: Uuid) -> Option<bool> {
+        let entries = self.entries.read().unwrap();
+        let (revoked, checked_at) = entries.get(&session_id)?;
+
+        (checked_at.elapsed() < self.ttl).then_some(*revoked)
+    }
+
+    pub fn insert(&self, session_id: Uuid, revoked: bool) {
+        let mut entries = self.entries.write().unwrap();
+
+        if entries.len() >= SWEEP_THRESHOLD {
+            entries.retain(|_, (_, checked_at)| checked_at.elapsed() < self.ttl);
+        }
+
+        entries.insert(session_id, (revoked, Instant::now()));
+    }
+}
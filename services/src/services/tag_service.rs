@@ -0,0 +1,47 @@
+use crate::{
+    errors::NoteError,
+    models::{TagCount, tag::normalize_tag},
+    repositories::traits::TagRepositoryTrait,
+    services::traits::TagServiceTrait,
+};
+use async_trait::async_trait;
+use std::sync::Arc;
+use tracing::instrument;
+use uuid::Uuid;
+
+pub struct TagService {
+    tag_repository: Arc<dyn TagRepositoryTrait>,
+}
+
+impl TagService {
+    pub fn new(tag_repository: Arc<dyn TagRepositoryTrait>) -> Self {
+        Self { tag_repository }
+    }
+}
+
+#[async_trait]
+impl TagServiceTrait for TagService {
+    #[instrument(skip_all, fields(user_id = %user_id))]
+    async fn find_tags(&self, user_id: Uuid) -> Result<Vec<TagCount>, NoteError> {
+        self.tag_repository.find_all_with_counts(user_id).await
+    }
+
+    #[instrument(skip_all, fields(user_id = %user_id))]
+    async fn rename_tag(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        new_name: &str,
+    ) -> Result<Option<TagCount>, NoteError> {
+        let new_name = normalize_tag(new_name);
+        if new_name.is_empty() {
+            return Err(NoteError::Validation(
+                "Tag name cannot be empty".to_string(),
+            ));
+        }
+
+        self.tag_repository
+            .rename(user_id, &normalize_tag(name), &new_name)
+            .await
+    }
+}
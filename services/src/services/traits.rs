@@ -1,7 +1,18 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
 use uuid::Uuid;
 
-use crate::{User, models::Note};
+pub use crate::errors::AuthError;
+use crate::{
+    User,
+    errors::{NoteError, UserError},
+    models::{
+        AuthTokens, ClientInfo, Credential, LoginOutcome, Note, NoteListFilter, NotePage,
+        NoteRevision, NoteSearchFilter, NoteSearchPage, PersonalAccessToken, Principal,
+        RevisionDiff, Scope, Session, TagCount, TotpEnrollment,
+    },
+    services::jwt_keys::JwkSet,
+};
 
 #[async_trait]
 pub trait UserServiceTrait: Send + Sync {
@@ -10,39 +21,31 @@ pub trait UserServiceTrait: Send + Sync {
         username: &str,
         email: &str,
         password_hash: &str,
-    ) -> Result<User, sqlx::Error>;
+    ) -> Result<User, UserError>;
 
-    async fn find_user_by_id(&self, id: Uuid) -> Result<Option<User>, sqlx::Error>;
+    async fn find_user_by_id(&self, id: Uuid) -> Result<Option<User>, UserError>;
 
-    async fn find_user_by_email(&self, email: &str) -> Result<Option<User>, sqlx::Error>;
+    async fn find_user_by_email(&self, email: &str) -> Result<Option<User>, UserError>;
 
-    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error>;
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, UserError>;
 
+    // For bio and image, `None` leaves the value alone and `Some(None)` clears it
     async fn update_user(
         &self,
         id: Uuid,
         username: Option<&str>,
         email: Option<&str>,
-        bio: Option<&str>,
-        image: Option<&str>,
-    ) -> Result<Option<User>, sqlx::Error>;
-}
+        bio: Option<Option<&str>>,
+        image: Option<Option<&str>>,
+    ) -> Result<Option<User>, UserError>;
 
-#[derive(Debug)]
-pub enum AuthError {
-    UserNotFound,
-    InvalidPassword,
-    UserAlreadyExists,
-    DatabaseError(sqlx::Error),
-    PasswordHashError,
-    TokenGenerationError,
-    TokenValidationError,
-}
+    async fn update_password(
+        &self,
+        id: Uuid,
+        password_hash: &str,
+    ) -> Result<Option<User>, UserError>;
 
-impl From<sqlx::Error> for AuthError {
-    fn from(err: sqlx::Error) -> Self {
-        AuthError::DatabaseError(err)
-    }
+    async fn mark_email_verified(&self, id: Uuid) -> Result<Option<User>, UserError>;
 }
 
 #[async_trait]
@@ -52,13 +55,109 @@ pub trait AuthServiceTrait: Send + Sync {
         username: &str,
         email: &str,
         password: &str,
-    ) -> Result<(User, String), AuthError>;
+        client: &ClientInfo,
+    ) -> Result<(User, AuthTokens), AuthError>;
+
+    // Returns a pending challenge instead of tokens when two-factor auth is on
+    async fn login_user(
+        &self,
+        email: &str,
+        password: &str,
+        client: &ClientInfo,
+    ) -> Result<LoginOutcome, AuthError>;
+
+    // Exchanges the challenge from login_user and a TOTP or recovery code for tokens
+    async fn verify_mfa(
+        &self,
+        mfa_token: &str,
+        code: &str,
+        client: &ClientInfo,
+    ) -> Result<(User, AuthTokens), AuthError>;
+
+    // Replaces an earlier secret that was never confirmed
+    async fn enroll_totp(&self, user: &User) -> Result<TotpEnrollment, AuthError>;
+
+    // Returns the recovery codes, which are only ever shown this once
+    async fn confirm_totp(&self, user_id: Uuid, code: &str) -> Result<Vec<String>, AuthError>;
+
+    async fn disable_totp(&self, user_id: Uuid, code: &str) -> Result<(), AuthError>;
+
+    async fn regenerate_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code: &str,
+    ) -> Result<Vec<String>, AuthError>;
+
+    async fn get_current_user(
+        &self,
+        user: User,
+        credential: &Credential,
+    ) -> Result<(User, Option<String>), AuthError>;
+
+    async fn validate_token(&self, token: &str) -> Result<Principal, AuthError>;
+
+    // The user a session token was issued to, from its signature alone. Revocation isn't
+    // looked up, so this is only fit for bucketing requests, never for authorizing them.
+    fn token_subject(&self, token: &str) -> Option<Uuid>;
+
+    // Public keys that access tokens can be verified with
+    fn jwks(&self) -> &JwkSet;
+
+    async fn refresh_tokens(
+        &self,
+        refresh_token: &str,
+        client: &ClientInfo,
+    ) -> Result<(User, AuthTokens), AuthError>;
+
+    async fn find_sessions(&self, user_id: Uuid) -> Result<Vec<Session>, AuthError>;
+
+    async fn revoke_session(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, AuthError>;
+
+    async fn revoke_all_sessions(&self, user_id: Uuid) -> Result<u64, AuthError>;
+
+    // Confirms the user knows their password before a sensitive change
+    async fn verify_password(&self, user: &User, password: &str) -> Result<(), AuthError>;
+
+    // Keeps the current session and revokes every other one
+    async fn change_password(
+        &self,
+        user: &User,
+        current_session_id: Uuid,
+        current_password: &str,
+        new_password: &str,
+    ) -> Result<(), AuthError>;
+
+    async fn request_password_reset(&self, email: &str) -> Result<(), AuthError>;
+
+    async fn reset_password(&self, token: &str, new_password: &str) -> Result<(), AuthError>;
+
+    // Replaces any verification link sent earlier
+    async fn send_verification_email(&self, user: &User) -> Result<(), AuthError>;
+
+    async fn verify_email(&self, token: &str) -> Result<User, AuthError>;
 
-    async fn login_user(&self, email: &str, password: &str) -> Result<(User, String), AuthError>;
+    // Forgets failed logins too old to count towards a lockout
+    async fn purge_login_attempts(&self) -> Result<u64, AuthError>;
 
-    async fn get_current_user(&self, user: User) -> Result<(User, String), AuthError>;
+    // Returns the stored token together with its secret, which is never shown again
+    async fn create_personal_access_token(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        scopes: &[Scope],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(PersonalAccessToken, String), AuthError>;
+
+    async fn find_personal_access_tokens(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<PersonalAccessToken>, AuthError>;
 
-    async fn validate_token(&self, token: &str) -> Result<uuid::Uuid, AuthError>;
+    async fn revoke_personal_access_token(
+        &self,
+        user_id: Uuid,
+        token_id: Uuid,
+    ) -> Result<bool, AuthError>;
 }
 
 #[async_trait]
@@ -68,15 +167,18 @@ pub trait NoteServiceTrait: Send + Sync {
         user_id: Uuid,
         title: &str,
         content: &str,
-    ) -> Result<Note, sqlx::Error>;
+        tags: &[String],
+    ) -> Result<Note, NoteError>;
 
     async fn find_note_by_id(
         &self,
         note_id: Uuid,
         user_id: Uuid,
-    ) -> Result<Option<Note>, sqlx::Error>;
+    ) -> Result<Option<Note>, NoteError>;
 
-    async fn find_notes_by_user_id(&self, user_id: Uuid) -> Result<Vec<Note>, sqlx::Error>;
+    async fn find_notes(&self, filter: NoteListFilter) -> Result<NotePage, NoteError>;
+
+    async fn search_notes(&self, filter: NoteSearchFilter) -> Result<NoteSearchPage, NoteError>;
 
     async fn update_note(
         &self,
@@ -84,7 +186,57 @@ pub trait NoteServiceTrait: Send + Sync {
         user_id: Uuid,
         title: Option<&str>,
         content: Option<&str>,
-    ) -> Result<Option<Note>, sqlx::Error>;
+        tags: Option<&[String]>,
+        expected_versions: Option<&[i32]>,
+    ) -> Result<Note, NoteError>;
+
+    async fn delete_note(&self, note_id: Uuid, user_id: Uuid) -> Result<Option<Note>, NoteError>;
+
+    async fn find_deleted_notes(&self, user_id: Uuid) -> Result<Vec<Note>, NoteError>;
+
+    async fn restore_note(&self, note_id: Uuid, user_id: Uuid) -> Result<Option<Note>, NoteError>;
+
+    async fn empty_trash(&self, user_id: Uuid) -> Result<u64, NoteError>;
+
+    async fn purge_trash(&self, retention: Duration) -> Result<u64, NoteError>;
+
+    async fn find_revisions(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<NoteRevision>, NoteError>;
 
-    async fn delete_note(&self, note_id: Uuid) -> Result<Option<Note>, sqlx::Error>;
+    async fn find_revision(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+        revision_number: i32,
+    ) -> Result<Option<NoteRevision>, NoteError>;
+
+    async fn restore_revision(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+        revision_number: i32,
+    ) -> Result<Option<Note>, NoteError>;
+
+    async fn diff_revisions(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+        from: i32,
+        to: i32,
+    ) -> Result<Option<RevisionDiff>, NoteError>;
+}
+
+#[async_trait]
+pub trait TagServiceTrait: Send + Sync {
+    async fn find_tags(&self, user_id: Uuid) -> Result<Vec<TagCount>, NoteError>;
+
+    async fn rename_tag(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        new_name: &str,
+    ) -> Result<Option<TagCount>, NoteError>;
 }
@@ -1,6 +1,9 @@
-use crate::{models::User, repositories::UserRepositoryTrait, services::UserServiceTrait};
+use crate::{
+    errors::UserError, models::User, repositories::UserRepositoryTrait, services::UserServiceTrait,
+};
 use async_trait::async_trait;
 use std::sync::Arc;
+use tracing::instrument;
 use uuid::Uuid;
 
 pub struct UserService {
@@ -15,39 +18,60 @@ impl UserService {
 
 #[async_trait]
 impl UserServiceTrait for UserService {
+    #[instrument(skip_all)]
     async fn create_user(
         &self,
         username: &str,
         email: &str,
         password_hash: &str,
-    ) -> Result<User, sqlx::Error> {
+    ) -> Result<User, UserError> {
         self.user_repository
             .create(username, email, password_hash)
             .await
     }
 
-    async fn find_user_by_id(&self, id: Uuid) -> Result<Option<User>, sqlx::Error> {
+    #[instrument(skip_all, fields(id = %id))]
+    async fn find_user_by_id(&self, id: Uuid) -> Result<Option<User>, UserError> {
         self.user_repository.find_by_id(id).await
     }
 
-    async fn find_user_by_email(&self, email: &str) -> Result<Option<User>, sqlx::Error> {
+    #[instrument(skip_all)]
+    async fn find_user_by_email(&self, email: &str) -> Result<Option<User>, UserError> {
         self.user_repository.find_by_email(email).await
     }
 
-    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error> {
+    #[instrument(skip_all)]
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, UserError> {
         self.user_repository.find_by_username(username).await
     }
 
+    #[instrument(skip_all, fields(id = %id))]
     async fn update_user(
         &self,
         id: Uuid,
         username: Option<&str>,
         email: Option<&str>,
-        bio: Option<&str>,
-        image: Option<&str>,
-    ) -> Result<Option<User>, sqlx::Error> {
+        bio: Option<Option<&str>>,
+        image: Option<Option<&str>>,
+    ) -> Result<Option<User>, UserError> {
         self.user_repository
             .update(id, username, email, bio, image)
             .await
     }
+
+    #[instrument(skip_all, fields(id = %id))]
+    async fn update_password(
+        &self,
+        id: Uuid,
+        password_hash: &str,
+    ) -> Result<Option<User>, UserError> {
+        self.user_repository
+            .update_password(id, password_hash)
+            .await
+    }
+
+    #[instrument(skip_all, fields(id = %id))]
+    async fn mark_email_verified(&self, id: Uuid) -> Result<Option<User>, UserError> {
+        self.user_repository.mark_email_verified(id).await
+    }
 }